@@ -0,0 +1,145 @@
+use super::Arch;
+use crate::plain::PlainData;
+
+/// The integer registers of an aarch64 process. `syscall` only
+/// defines the ones of the architecture it's built for.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct IntRegisters {
+    pub elr_el1: u64,
+    pub tpidr_el0: u64,
+    pub tpidrro_el0: u64,
+    pub spsr_el1: u64,
+    pub esr_el1: u64,
+    pub sp_el0: u64,
+    pub padding: u64,
+    /// `x30` to `x0`, in that order
+    pub x: [u64; 31],
+}
+// Nothing but `u64`s
+unsafe impl PlainData for IntRegisters {}
+impl IntRegisters {
+    /// General purpose register `xN`
+    pub fn get(&self, n: usize) -> u64 {
+        self.x[30 - n]
+    }
+    pub fn set(&mut self, n: usize, value: u64) {
+        self.x[30 - n] = value;
+    }
+}
+
+pub struct Aarch64;
+
+impl Arch for Aarch64 {
+    type Registers = IntRegisters;
+
+    // brk #0
+    const BREAKPOINT: &'static [u8] = &[0x00, 0x00, 0x20, 0xD4];
+    // The exception link register points at the `brk` itself
+    const BREAKPOINT_PC_ADJUSTMENT: usize = 0;
+    const MAX_INSTRUCTION_LEN: usize = 4;
+
+    fn syscall_number(r: &IntRegisters) -> usize {
+        r.get(8) as usize
+    }
+    fn syscall_args(r: &IntRegisters) -> [usize; 6] {
+        [0, 1, 2, 3, 4, 5].map(|n| r.get(n) as usize)
+    }
+    fn set_syscall_arg(r: &mut IntRegisters, index: usize, value: usize) {
+        assert!(index < 6, "syscall argument {} out of range", index);
+        r.set(index, value as u64);
+    }
+    fn return_value(r: &IntRegisters) -> usize {
+        r.get(0) as usize
+    }
+    fn set_return_value(r: &mut IntRegisters, value: usize) {
+        r.set(0, value as u64);
+    }
+    fn instruction_pointer(r: &IntRegisters) -> usize {
+        r.elr_el1 as usize
+    }
+    fn set_instruction_pointer(r: &mut IntRegisters, address: usize) {
+        r.elr_el1 = address as u64;
+    }
+    fn stack_pointer(r: &IntRegisters) -> usize {
+        r.sp_el0 as usize
+    }
+    fn frame_pointer(r: &IntRegisters) -> usize {
+        r.get(29) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers() -> IntRegisters {
+        let mut r = IntRegisters {
+            elr_el1: 0x40_1000,
+            sp_el0: 0x7FFF_0000,
+            ..Default::default()
+        };
+        for n in 0..6 {
+            r.set(n, 10 + n as u64);
+        }
+        r.set(8, 3);
+        r.set(29, 0x7FFF_0040);
+        r
+    }
+
+    #[test]
+    fn register_order() {
+        let r = registers();
+        assert_eq!(r.x[30], 10);
+        assert_eq!(r.x[22], 3);
+        assert_eq!(r.x[1], 0x7FFF_0040);
+    }
+    #[test]
+    fn syscall_args() {
+        let mut r = registers();
+        assert_eq!(Aarch64::syscall_number(&r), 3);
+        assert_eq!(Aarch64::syscall_args(&r), [10, 11, 12, 13, 14, 15]);
+
+        for i in 0..6 {
+            Aarch64::set_syscall_arg(&mut r, i, 20 + i);
+        }
+        assert_eq!(Aarch64::syscall_args(&r), [20, 21, 22, 23, 24, 25]);
+        assert_eq!(r.get(5), 25);
+    }
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn syscall_arg_out_of_range() {
+        Aarch64::set_syscall_arg(&mut registers(), 6, 0);
+    }
+    #[test]
+    fn return_value() {
+        let mut r = registers();
+        assert_eq!(Aarch64::return_value(&r), 10);
+
+        Aarch64::set_return_value(
+            &mut r,
+            syscall::Error::mux(Err(syscall::Error::new(syscall::ENOENT))),
+        );
+        assert_eq!(r.get(0), -syscall::ENOENT as u64);
+        assert_eq!(
+            syscall::Error::demux(Aarch64::return_value(&r)).map_err(|err| err.errno),
+            Err(syscall::ENOENT)
+        );
+    }
+    #[test]
+    fn pointers() {
+        let mut r = registers();
+        assert_eq!(Aarch64::instruction_pointer(&r), 0x40_1000);
+        assert_eq!(Aarch64::stack_pointer(&r), 0x7FFF_0000);
+        assert_eq!(Aarch64::frame_pointer(&r), 0x7FFF_0040);
+
+        Aarch64::set_instruction_pointer(&mut r, 0x40_2000);
+        assert_eq!(r.elr_el1, 0x40_2000);
+    }
+    #[test]
+    fn breakpoint() {
+        // brk #0 is 0xD4200000, stored little-endian
+        assert_eq!(Aarch64::BREAKPOINT, &0xD420_0000u32.to_le_bytes());
+        assert_eq!(Aarch64::BREAKPOINT_PC_ADJUSTMENT, 0);
+    }
+}
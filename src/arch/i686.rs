@@ -0,0 +1,141 @@
+use super::Arch;
+use crate::plain::PlainData;
+
+/// The integer registers of a 32-bit x86 process. `syscall` only
+/// defines the ones of the architecture it's built for.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct IntRegisters {
+    pub ebp: u32,
+    pub esi: u32,
+    pub edi: u32,
+    pub ebx: u32,
+    pub eax: u32,
+    pub ecx: u32,
+    pub edx: u32,
+    pub eip: u32,
+    pub cs: u32,
+    pub eflags: u32,
+    pub esp: u32,
+    pub ss: u32,
+    pub fs: u32,
+}
+// Nothing but `u32`s
+unsafe impl PlainData for IntRegisters {}
+
+pub struct I686;
+
+impl Arch for I686 {
+    type Registers = IntRegisters;
+
+    const BREAKPOINT: &'static [u8] = &[0xCC]; // int3
+    const BREAKPOINT_PC_ADJUSTMENT: usize = 1;
+    const MAX_INSTRUCTION_LEN: usize = 15;
+
+    fn syscall_number(r: &IntRegisters) -> usize {
+        r.eax as usize
+    }
+    fn syscall_args(r: &IntRegisters) -> [usize; 6] {
+        [r.ebx, r.ecx, r.edx, r.esi, r.edi, r.ebp].map(|reg| reg as usize)
+    }
+    fn set_syscall_arg(r: &mut IntRegisters, index: usize, value: usize) {
+        let reg = match index {
+            0 => &mut r.ebx,
+            1 => &mut r.ecx,
+            2 => &mut r.edx,
+            3 => &mut r.esi,
+            4 => &mut r.edi,
+            5 => &mut r.ebp,
+            _ => panic!("syscall argument {} out of range", index),
+        };
+        *reg = value as u32;
+    }
+    fn return_value(r: &IntRegisters) -> usize {
+        // Sign extended, so errors still demux on a 64-bit tracer
+        r.eax as i32 as usize
+    }
+    fn set_return_value(r: &mut IntRegisters, value: usize) {
+        r.eax = value as u32;
+    }
+    fn instruction_pointer(r: &IntRegisters) -> usize {
+        r.eip as usize
+    }
+    fn set_instruction_pointer(r: &mut IntRegisters, address: usize) {
+        r.eip = address as u32;
+    }
+    fn stack_pointer(r: &IntRegisters) -> usize {
+        r.esp as usize
+    }
+    fn frame_pointer(r: &IntRegisters) -> usize {
+        r.ebp as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers() -> IntRegisters {
+        IntRegisters {
+            eax: 3,
+            ebx: 10,
+            ecx: 11,
+            edx: 12,
+            esi: 13,
+            edi: 14,
+            ebp: 15,
+            eip: 0x0804_8000,
+            esp: 0xBFFF_0000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn syscall_args() {
+        let mut r = registers();
+        assert_eq!(I686::syscall_number(&r), 3);
+        assert_eq!(I686::syscall_args(&r), [10, 11, 12, 13, 14, 15]);
+
+        for i in 0..6 {
+            I686::set_syscall_arg(&mut r, i, 20 + i);
+        }
+        assert_eq!(I686::syscall_args(&r), [20, 21, 22, 23, 24, 25]);
+        assert_eq!(r.ebp, 25);
+    }
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn syscall_arg_out_of_range() {
+        I686::set_syscall_arg(&mut registers(), 6, 0);
+    }
+    #[test]
+    fn return_value() {
+        let mut r = registers();
+        assert_eq!(I686::return_value(&r), 3);
+
+        // The 32-bit kernel returns -ENOENT
+        r.eax = -syscall::ENOENT as u32;
+        assert_eq!(I686::return_value(&r), -syscall::ENOENT as usize);
+        assert_eq!(
+            syscall::Error::demux(I686::return_value(&r)).map_err(|err| err.errno),
+            Err(syscall::ENOENT)
+        );
+
+        I686::set_return_value(&mut r, 0xFFFF_FFFF_0000_0001);
+        assert_eq!(r.eax, 1);
+    }
+    #[test]
+    fn pointers() {
+        let mut r = registers();
+        assert_eq!(I686::instruction_pointer(&r), 0x0804_8000);
+        assert_eq!(I686::stack_pointer(&r), 0xBFFF_0000);
+        assert_eq!(I686::frame_pointer(&r), 15);
+
+        I686::set_instruction_pointer(&mut r, 0x0804_9000);
+        assert_eq!(r.eip, 0x0804_9000);
+    }
+    #[test]
+    fn breakpoint() {
+        assert_eq!(I686::BREAKPOINT, &[0xCC]);
+        assert_eq!(I686::BREAKPOINT_PC_ADJUSTMENT, 1);
+    }
+}
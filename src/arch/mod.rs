@@ -1,3 +1,72 @@
+//! What the tracer needs to know about each CPU architecture: where
+//! the registers of a stopped process keep the syscall, its arguments
+//! and the various pointers, and how to set a breakpoint.
+
+use std::fmt;
+
+use crate::{
+    plain::PlainData,
+    syscalls::{self, DecodedSyscall},
+    Memory,
+};
+
+mod aarch64;
+mod i686;
+// `syscall` only defines the x86_64 registers when built for it
+#[cfg(target_arch = "x86_64")]
 mod x86_64;
 
-pub use self::x86_64::*;
+#[cfg(target_arch = "x86_64")]
+pub use self::x86_64::X86_64;
+pub use self::{aarch64::Aarch64, i686::I686};
+
+/// The architecture this crate is built for
+#[cfg(target_arch = "x86_64")]
+pub type Native = X86_64;
+/// The architecture this crate is built for
+#[cfg(target_arch = "aarch64")]
+pub type Native = Aarch64;
+/// The architecture this crate is built for
+#[cfg(target_arch = "x86")]
+pub type Native = I686;
+
+/// The register layout and conventions of one architecture
+pub trait Arch {
+    /// The integer registers, as the kernel lays them out in
+    /// `proc:<pid>/regs/int`
+    type Registers: PlainData + fmt::Debug + Default;
+
+    /// The instruction that traps into the tracer, in memory order
+    const BREAKPOINT: &'static [u8];
+    /// The number of bytes the instruction pointer has moved past a
+    /// breakpoint when it's reported
+    const BREAKPOINT_PC_ADJUSTMENT: usize;
+    /// The maximum length of one instruction
+    const MAX_INSTRUCTION_LEN: usize;
+
+    fn syscall_number(r: &Self::Registers) -> usize;
+    fn syscall_args(r: &Self::Registers) -> [usize; 6];
+    /// Set one of the six syscall arguments. Panics if `index` is out
+    /// of range.
+    fn set_syscall_arg(r: &mut Self::Registers, index: usize, value: usize);
+    fn return_value(r: &Self::Registers) -> usize;
+    fn set_return_value(r: &mut Self::Registers, value: usize);
+    fn instruction_pointer(r: &Self::Registers) -> usize;
+    fn set_instruction_pointer(r: &mut Self::Registers, address: usize);
+    fn stack_pointer(r: &Self::Registers) -> usize;
+    /// The frame pointer, which is only meaningful if the running code
+    /// was built to keep one
+    fn frame_pointer(r: &Self::Registers) -> usize;
+
+    /// Decode the syscall these registers describe, reading up to
+    /// `limit` bytes of any byte buffer
+    fn decode_syscall(
+        mem: Option<&mut Memory>,
+        r: &Self::Registers,
+        limit: usize,
+    ) -> DecodedSyscall {
+        // No Redox syscall takes more than five arguments
+        let args = Self::syscall_args(r);
+        syscalls::decode(mem, Self::syscall_number(r), &args[..5], limit)
+    }
+}
@@ -1,13 +1,122 @@
-use crate::{kernel::debug, Memory};
-use std::io::Result;
 use syscall::data::IntRegisters;
 
-pub fn format_syscall(mem: Option<&mut Memory>, r: &IntRegisters) -> String {
-    debug::format_call(mem, r.rax, r.rdi, r.rsi, r.rdx, r.r10, r.r8)
-}
-pub fn set_breakpoint(mem: &mut Memory, address: *const u8) -> Result<()> {
-    mem.write(address, &[0xCC]) // int3
+use super::Arch;
+use crate::plain::PlainData;
+
+// Nothing but `usize`s
+unsafe impl PlainData for IntRegisters {}
+
+pub struct X86_64;
+
+impl Arch for X86_64 {
+    type Registers = IntRegisters;
+
+    const BREAKPOINT: &'static [u8] = &[0xCC]; // int3
+    const BREAKPOINT_PC_ADJUSTMENT: usize = 1;
+    const MAX_INSTRUCTION_LEN: usize = 15;
+
+    fn syscall_number(r: &IntRegisters) -> usize {
+        r.rax
+    }
+    fn syscall_args(r: &IntRegisters) -> [usize; 6] {
+        [r.rdi, r.rsi, r.rdx, r.r10, r.r8, r.r9]
+    }
+    fn set_syscall_arg(r: &mut IntRegisters, index: usize, value: usize) {
+        let reg = match index {
+            0 => &mut r.rdi,
+            1 => &mut r.rsi,
+            2 => &mut r.rdx,
+            3 => &mut r.r10,
+            4 => &mut r.r8,
+            5 => &mut r.r9,
+            _ => panic!("syscall argument {} out of range", index),
+        };
+        *reg = value;
+    }
+    fn return_value(r: &IntRegisters) -> usize {
+        r.rax
+    }
+    fn set_return_value(r: &mut IntRegisters, value: usize) {
+        r.rax = value;
+    }
+    fn instruction_pointer(r: &IntRegisters) -> usize {
+        r.rip
+    }
+    fn set_instruction_pointer(r: &mut IntRegisters, address: usize) {
+        r.rip = address;
+    }
+    fn stack_pointer(r: &IntRegisters) -> usize {
+        r.rsp
+    }
+    fn frame_pointer(r: &IntRegisters) -> usize {
+        r.rbp
+    }
 }
-pub fn return_value(r: &IntRegisters) -> usize {
-    r.rax
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers() -> IntRegisters {
+        IntRegisters {
+            rax: 3,
+            rdi: 10,
+            rsi: 11,
+            rdx: 12,
+            r10: 13,
+            r8: 14,
+            r9: 15,
+            rip: 0x40_1000,
+            rsp: 0x7FFF_0000,
+            rbp: 0x7FFF_0040,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn syscall_args() {
+        let mut r = registers();
+        assert_eq!(X86_64::syscall_number(&r), 3);
+        assert_eq!(X86_64::syscall_args(&r), [10, 11, 12, 13, 14, 15]);
+
+        for i in 0..6 {
+            X86_64::set_syscall_arg(&mut r, i, 20 + i);
+        }
+        assert_eq!(X86_64::syscall_args(&r), [20, 21, 22, 23, 24, 25]);
+        assert_eq!(r.r10, 23);
+    }
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn syscall_arg_out_of_range() {
+        X86_64::set_syscall_arg(&mut registers(), 6, 0);
+    }
+    #[test]
+    fn return_value() {
+        let mut r = registers();
+        assert_eq!(X86_64::return_value(&r), 3);
+
+        X86_64::set_return_value(
+            &mut r,
+            syscall::Error::mux(Err(syscall::Error::new(syscall::ENOENT))),
+        );
+        assert_eq!(
+            syscall::Error::demux(X86_64::return_value(&r)).map_err(|err| err.errno),
+            Err(syscall::ENOENT)
+        );
+    }
+    #[test]
+    fn pointers() {
+        let mut r = registers();
+        assert_eq!(X86_64::instruction_pointer(&r), 0x40_1000);
+        assert_eq!(X86_64::stack_pointer(&r), 0x7FFF_0000);
+        assert_eq!(X86_64::frame_pointer(&r), 0x7FFF_0040);
+
+        X86_64::set_instruction_pointer(&mut r, 0x40_2000);
+        assert_eq!(r.rip, 0x40_2000);
+    }
+    #[test]
+    fn breakpoint() {
+        assert_eq!(X86_64::BREAKPOINT, &[0xCC]);
+        assert_eq!(X86_64::BREAKPOINT_PC_ADJUSTMENT, 1);
+    }
 }
@@ -0,0 +1,74 @@
+//! Walking the call stack of a stopped process by following the chain
+//! of saved frame pointers
+
+use std::mem;
+
+use crate::{IntRegisters, Memory};
+
+const WORD: usize = mem::size_of::<usize>();
+
+/// Why a backtrace stopped where it did
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum End {
+    /// The outermost frame was reached
+    Complete,
+    /// There were more frames than the limit
+    Limit,
+    /// The frame at this address could not be read
+    Unreadable(usize),
+    /// The frame pointer led somewhere no frame can be, such as below
+    /// the stack pointer. Usually this means some function on the way
+    /// was compiled without frame pointers.
+    Corrupt(usize),
+}
+
+/// The return addresses of the active function calls of a process,
+/// innermost first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backtrace {
+    /// The instruction pointer, followed by the return address of each
+    /// frame
+    pub frames: Vec<usize>,
+    pub end: End,
+}
+impl Backtrace {
+    /// Walk the stack described by `regs`, collecting at most `limit`
+    /// addresses.
+    ///
+    /// This relies on every function saving the frame pointer of its
+    /// caller next to its return address, so functions built without
+    /// frame pointers are skipped or end the walk early.
+    pub fn walk(regs: &IntRegisters, mem: &mut Memory, limit: usize) -> Self {
+        let mut frames = vec![regs.instruction_pointer()];
+        let mut lowest = regs.stack_pointer();
+        let mut frame = regs.frame_pointer();
+
+        let end = loop {
+            if frame == 0 {
+                break End::Complete;
+            }
+            if frames.len() >= limit {
+                break End::Limit;
+            }
+            // The stack grows down, so each frame is above the last
+            if frame < lowest || frame.checked_rem(WORD) != Some(0) {
+                break End::Corrupt(frame);
+            }
+
+            // The saved frame pointer, followed by the return address
+            let [saved, ret] = match mem.read_value(frame as *const [usize; 2]) {
+                Ok(record) => record,
+                Err(_) => break End::Unreadable(frame),
+            };
+
+            if ret == 0 {
+                break End::Complete;
+            }
+            frames.push(ret);
+            lowest = frame + 2 * WORD;
+            frame = saved;
+        };
+
+        Self { frames, end }
+    }
+}
@@ -1,15 +1,34 @@
 use std::{
     collections::HashMap,
-    fs::File,
-    io::{prelude::*, ErrorKind, Result},
-    os::unix::io::AsRawFd,
+    io::{ErrorKind, Result},
+    mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
 };
-use syscall::{data::Event, flag::EVENT_READ};
 
-use strace::{EventData, Flags, NonblockTracer, Pid, Tracer};
+use strace::{
+    backtrace::Backtrace,
+    elf::Symbols,
+    fds::FdTable,
+    session::TraceSession,
+    syscalls::{DecodedSyscall, RenderOptions},
+    Event, EventData, Flags, Pid, Tracer,
+};
 
 use structopt::StructOpt;
 
+use super::{
+    filter::{AddressRange, Expr, Filter},
+    inject::Rule,
+    interrupt,
+    output::Output,
+    sink::{Format, JsonSink, Record, RecordKind, Sink, TextSink},
+    stats::Stats,
+    timestamp::Clock,
+    tree::ProcessTree,
+};
+
 #[derive(StructOpt)]
 // Only make `pub` features that are in both simple and advanced modes
 pub struct Opt {
@@ -17,6 +36,91 @@ pub struct Opt {
     /// Specify whether or not strace should trace more than just the
     /// top level child process
     recursive: bool,
+    #[structopt(
+        short = "p",
+        number_of_values = 1,
+        required_unless = "cmd",
+        conflicts_with = "cmd"
+    )]
+    /// Attach to an already running process instead of starting a
+    /// command. May be specified multiple times
+    pub pids: Vec<Pid>,
+    #[structopt(short = "e", number_of_values = 1)]
+    /// Qualify which events to trace, such as `trace=open,%file`,
+    /// `trace=!futex,yield` or `signal=SIGSEGV,SIGCHLD`
+    expr: Vec<Expr>,
+    #[structopt(short = "c", long = "summary")]
+    /// Instead of printing each syscall, count calls, errors and time
+    /// spent per syscall and print a summary when tracing ends
+    pub summary: bool,
+    #[structopt(short = "t", parse(from_occurrences))]
+    /// Prefix each line with the wall clock time. Specify twice to
+    /// include microseconds
+    timestamps: u8,
+    #[structopt(short = "R", long = "relative-timestamps")]
+    /// Prefix each line with the time elapsed since the previous line
+    relative_timestamps: bool,
+    #[structopt(short = "T")]
+    /// Show the time spent inside each syscall
+    syscall_times: bool,
+    #[structopt(short = "s", default_value = "32")]
+    /// Maximum number of bytes of each read or write buffer to print
+    string_limit: usize,
+    #[structopt(short = "x")]
+    /// Print read and write buffers as hex escapes
+    hex: bool,
+    #[structopt(long = "dump-io", use_delimiter = true)]
+    /// Print a full hex dump of all data read from or written to the
+    /// specified file descriptors
+    dump_io: Vec<usize>,
+    #[structopt(short = "y")]
+    /// Print the paths associated with file descriptor arguments
+    decode_fds: bool,
+    #[structopt(long = "tree")]
+    /// Print the tree of traced processes, with the programs they ran,
+    /// their runtimes and exit statuses, when tracing ends
+    tree: bool,
+    #[structopt(long = "inject", number_of_values = 1)]
+    /// Skip matching syscalls and make them fail or return a value of
+    /// your choosing, such as `open:error=ENOENT:when=2+3` or
+    /// `getpid:retval=1`. `when=N` only injects into the Nth matching
+    /// call, `when=N+` into every call from the Nth and `when=N+S`
+    /// into every Sth call from the Nth
+    inject: Vec<Rule>,
+    #[structopt(long = "instructions")]
+    /// Single-step the traced processes and log each instruction
+    /// executed
+    instructions: bool,
+    #[structopt(long = "instructions-range", requires = "instructions")]
+    /// Only log instructions within START-END, such as
+    /// `0x400000-0x401000`
+    instructions_range: Option<AddressRange>,
+    #[structopt(long = "count-instructions")]
+    /// Single-step the traced processes and log the number of
+    /// instructions executed before each syscall
+    count_instructions: bool,
+    #[structopt(long = "symbols")]
+    /// Show instruction, backtrace and signal handler addresses as
+    /// function and offset, using the symbol tables of the traced
+    /// programs
+    symbols: bool,
+    #[structopt(short = "k")]
+    /// Print a backtrace of the calling code under each syscall, by
+    /// following frame pointers. Best combined with `--symbols`
+    backtraces: bool,
+    #[structopt(long = "backtrace-limit", default_value = "16")]
+    /// Maximum number of frames to print with `-k`
+    backtrace_limit: usize,
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    /// Write the trace to a file instead of stderr
+    output: Option<PathBuf>,
+    #[structopt(long = "output-separately", requires = "output")]
+    /// Write the trace of each process to its own `FILE.<pid>`
+    output_separately: bool,
+    #[structopt(long = "format", default_value = "text")]
+    /// Output format, either `text` or `json` for one JSON object per
+    /// line
+    pub format: Format,
     /// Specify the command and arguments to run
     pub cmd: Vec<String>,
 }
@@ -25,103 +129,435 @@ pub fn parse_args() -> Opt {
     Opt::from_args()
 }
 
+impl Opt {
+    /// The stops to ask for each time a process is continued
+    fn trace_flags(&self) -> Flags {
+        if self.instructions || self.count_instructions {
+            crate::TRACE_FLAGS | Flags::STOP_SINGLESTEP
+        } else {
+            crate::TRACE_FLAGS
+        }
+    }
+}
+
+struct Pending {
+    syscall: DecodedSyscall,
+    start: Instant,
+    /// False if the syscall is only seen to keep track of file
+    /// descriptors, but was filtered out
+    traced: bool,
+    injected: bool,
+}
+
 struct Handle {
     pid: Pid,
-    tracer: NonblockTracer,
-    unclosed: Vec<String>,
+    unclosed: Vec<Option<Pending>>,
+    /// Only kept with `-y` or `--symbols`
+    fds: Option<FdTable>,
+    /// The symbols of the program being run, with `--symbols`
+    symbols: Option<Rc<Symbols>>,
+    /// The number of syscalls matched by each `--inject` rule so far
+    inject_counts: Vec<u64>,
+    /// The number of instructions stepped since the last syscall
+    instructions: u64,
 }
-
-pub fn inner_main(root: Pid, tracer: Tracer, opt: Opt) -> Result<()> {
-    let mut tracer = tracer.nonblocking()?;
-    tracer.next(crate::TRACE_FLAGS)?;
-
-    let mut events = File::open("event:")?;
-
-    let mut next_id = 0;
-    events.write(&Event {
-        id: tracer.file.as_raw_fd() as usize,
-        flags: EVENT_READ,
-        data: next_id,
-    })?;
-
-    let mut tracers = HashMap::new();
-    tracers.insert(
-        next_id,
-        Handle {
-            pid: root,
-            tracer,
+impl Handle {
+    fn new(pid: Pid, fds: Option<FdTable>, symbols: Option<Rc<Symbols>>, rules: usize) -> Self {
+        Self {
+            pid,
             unclosed: Vec::new(),
+            fds,
+            symbols,
+            inject_counts: vec![0; rules],
+            instructions: 0,
+        }
+    }
+    /// Create a record of something that happened just now, along
+    /// with what is known about the process
+    fn record<'a>(&'a self, opt: &Opt, kind: RecordKind<'a>) -> Record<'a> {
+        Record {
+            fds: self.fds.as_ref().filter(|_| opt.decode_fds),
+            symbols: self.symbols.as_deref(),
+            ..Record::now(self.pid, kind)
+        }
+    }
+}
+
+/// Load the symbols of a program for `--symbols`, or warn if that
+/// fails
+fn load_symbols(path: &Path) -> Option<Rc<Symbols>> {
+    match Symbols::load(path) {
+        Ok(symbols) => Some(Rc::new(symbols)),
+        Err(err) => {
+            eprintln!("Can't load symbols of {}: {}", path.display(), err);
+            None
         },
+    }
+}
+
+/// How many bytes of a syscall's buffers to read: what `-s` shows of
+/// reads and writes, unless they're dumped by `--dump-io`, and all of
+/// anything else
+fn buffer_limit(opt: &Opt, number: usize, fd: usize) -> usize {
+    match number {
+        syscall::SYS_READ | syscall::SYS_WRITE if !opt.dump_io.contains(&fd) => opt.string_limit,
+        _ => usize::MAX,
+    }
+}
+
+/// All traced processes, and what is known about each of them
+struct Tracees<'a> {
+    session: TraceSession,
+    handles: HashMap<Pid, Handle>,
+    tree: Option<&'a mut ProcessTree>,
+}
+impl<'a> Tracees<'a> {
+    /// Forget about a process that is gone
+    fn end(&mut self, pid: Pid) {
+        self.session.remove(pid);
+        self.handles.remove(&pid);
+        if let Some(tree) = self.tree.as_deref_mut() {
+            tree.end(pid);
+        }
+    }
+}
+
+pub fn inner_main(root: Pid, program: &Path, tracer: Tracer, opt: Opt) -> Result<()> {
+    let symbols = if opt.symbols {
+        load_symbols(program)
+    } else {
+        None
+    };
+    run(vec![(root, tracer, symbols)], opt)
+}
+
+/// Trace the already running processes specified with `-p`
+pub fn attach_main(opt: Opt) -> Result<()> {
+    let mut roots = Vec::with_capacity(opt.pids.len());
+    for &pid in &opt.pids {
+        roots.push((pid, Tracer::attach(pid)?, None));
+        eprintln!("Attached to PID {}", pid);
+    }
+    run(roots, opt)
+}
+
+fn run(roots: Vec<(Pid, Tracer, Option<Rc<Symbols>>)>, opt: Opt) -> Result<()> {
+    let mut output = match opt.output {
+        Some(ref path) => Output::create(path.clone(), opt.output_separately)?,
+        None => Output::stderr(),
+    };
+    let mut sink: Box<dyn Sink> = match opt.format {
+        Format::Text => Box::new(TextSink {
+            clock: Clock::new(opt.timestamps, opt.relative_timestamps),
+            syscall_times: opt.syscall_times,
+            show_pid: true,
+            render: RenderOptions {
+                string_limit: Some(opt.string_limit),
+                hex: opt.hex,
+                fds: None,
+            },
+            dump_io: opt.dump_io.clone(),
+        }),
+        Format::Json => Box::new(JsonSink),
+    };
+    let mut stats = if opt.summary {
+        Some(Stats::new(opt.recursive || roots.len() > 1))
+    } else {
+        None
+    };
+    let mut tree = if opt.tree {
+        Some(ProcessTree::new())
+    } else {
+        None
+    };
+
+    interrupt::catch()?;
+    let result = trace(
+        roots,
+        &opt,
+        &mut output,
+        &mut *sink,
+        stats.as_mut(),
+        tree.as_mut(),
     );
-    next_id += 1;
+
+    if let Some(stats) = stats {
+        stats.print(output.writer(None)?)?;
+    }
+    if let Some(tree) = tree {
+        tree.print(output.writer(None)?)?;
+    }
+    result
+}
+
+fn trace(
+    roots: Vec<(Pid, Tracer, Option<Rc<Symbols>>)>,
+    opt: &Opt,
+    output: &mut Output,
+    sink: &mut dyn Sink,
+    mut stats: Option<&mut Stats>,
+    tree: Option<&mut ProcessTree>,
+) -> Result<()> {
+    let filter = Filter::new(&opt.expr);
+
+    let mut session = TraceSession::new(opt.trace_flags())?;
+    session.set_follow_clones(opt.recursive);
+    let mut tracees = Tracees {
+        session,
+        handles: HashMap::new(),
+        tree,
+    };
+    for (pid, tracer, symbols) in roots {
+        if let Some(tree) = tracees.tree.as_deref_mut() {
+            tree.add_root(pid);
+        }
+        // With `--symbols`, descriptors are also needed to tell which
+        // program `fexec` runs
+        let fds = if (opt.decode_fds || opt.symbols) && !opt.summary {
+            Some(FdTable::new())
+        } else {
+            None
+        };
+        tracees.session.add(pid, tracer.nonblocking()?)?;
+        tracees
+            .handles
+            .insert(pid, Handle::new(pid, fds, symbols, opt.inject.len()));
+    }
 
     loop {
-        let mut event = Event::default();
-        events.read(&mut event)?;
-        let index = event.data;
+        if interrupt::requested() {
+            let pids: Vec<_> = tracees.session.pids().collect();
+            for pid in pids {
+                eprintln!("Detaching from PID {}", pid);
+                match tracees.session.detach(pid) {
+                    Err(ref err) if err.raw_os_error() == Some(syscall::ESRCH) => (),
+                    result => result?,
+                }
+            }
+            break;
+        }
 
-        let handle = tracers.get_mut(&index).unwrap();
+        let next = tracees.session.next_event();
+        for pid in tracees.session.take_lost() {
+            tracees.end(pid);
+        }
+        let (pid, event) = match next {
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+            result => match result? {
+                Some(next) => next,
+                None => break,
+            },
+        };
 
-        for event in handle.tracer.events()? {
-            let event = match event {
-                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
-                x => x?,
-            };
+        match handle_event(
+            (pid, event),
+            &mut tracees,
+            &filter,
+            opt,
+            output,
+            sink,
+            stats.as_deref_mut(),
+        ) {
+            Ok(flags) => tracees.session.continue_with(pid, flags),
+            // The process is gone, but others may still be running
+            Err(ref err) if err.raw_os_error() == Some(syscall::ESRCH) => tracees.end(pid),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
 
-            // We don't want to mutably borrow tracer across the
-            // entire loop - rather, re-fetch it at each iteration.
-            let handle = tracers.get_mut(&index).unwrap();
-
-            if event.cause == Flags::STOP_PRE_SYSCALL {
-                let regs = handle.tracer.regs.get_int()?;
-                let syscall = regs.format_syscall_full(&mut handle.tracer.mem);
-
-                eprintln!("SYSCALL     (pid {}): {}", handle.pid, syscall);
-                handle.unclosed.push(syscall);
-            } else if event.cause == Flags::STOP_POST_SYSCALL {
-                let syscall = handle.unclosed.pop();
-                let syscall = syscall
-                    .as_ref()
-                    .map(|s| &**s)
-                    .unwrap_or("<unmatched syscall>");
-
-                let regs = handle.tracer.regs.get_int()?;
-                let ret = regs.return_value();
-
-                eprint!("SYSCALL RET (pid {}): {} = ", handle.pid, syscall);
-                match syscall::Error::demux(ret) {
-                    Ok(val) => eprintln!("Ok({} ({:#X}))", val, val),
-                    Err(err) => eprintln!("Err(\"{}\" ({:#X})) ({:#X})", err, err.errno, ret),
+/// Handle one event of a traced process, which stays stopped until
+/// the session continues it. Returns any extra flags to continue it
+/// with.
+fn handle_event(
+    (pid, event): (Pid, Event),
+    tracees: &mut Tracees,
+    filter: &Filter,
+    opt: &Opt,
+    output: &mut Output,
+    sink: &mut dyn Sink,
+    mut stats: Option<&mut Stats>,
+) -> Result<Flags> {
+    let mut next_flags = Flags::empty();
+    let (handle, tracer) = match (tracees.handles.get_mut(&pid), tracees.session.tracer(pid)) {
+        (Some(handle), Some(tracer)) => (handle, tracer),
+        _ => return Ok(next_flags),
+    };
+
+    match event.data {
+        EventData::StopPreSyscall => {
+            let regs = tracer.regs.get_int()?;
+            let mut syscall = regs.decode_syscall(None, 0);
+            let limit = buffer_limit(opt, syscall.number, regs.args()[0]);
+            let instructions = mem::replace(&mut handle.instructions, 0);
+            if let Some(tree) = tracees.tree.as_deref_mut() {
+                if syscall.number == syscall::SYS_FEXEC {
+                    tree.exec(pid, &regs.decode_syscall(Some(&mut tracer.mem), limit));
                 }
-            } else {
-                eprintln!("OTHER EVENT: {:?}", event);
-
-                if opt.recursive {
-                    if let EventData::EventClone(pid) = event.data {
-                        let mut child = NonblockTracer::attach(pid)?;
-                        child.next(crate::TRACE_FLAGS)?;
-
-                        events.write(&Event {
-                            id: child.file.as_raw_fd() as usize,
-                            flags: EVENT_READ,
-                            data: next_id,
-                        })?;
-
-                        tracers.insert(
-                            next_id,
-                            Handle {
-                                pid,
-                                tracer: child,
-                                unclosed: Vec::new(),
-                            },
-                        );
-                        next_id += 1;
-                    }
+            }
+            if opt.symbols && syscall.number == syscall::SYS_FEXEC {
+                // Descriptors opened before tracing began are unknown,
+                // such as that of the program we started, whose symbols
+                // are already loaded. With a known one, the old program
+                // is gone even if the new one can't be read.
+                if let Some(path) = handle.fds.as_ref().and_then(|fds| fds.get(regs.args()[0])) {
+                    handle.symbols = load_symbols(Path::new(path));
+                }
+            }
+
+            let mut injected = false;
+            for (rule, count) in opt.inject.iter().zip(&mut handle.inject_counts) {
+                if !rule.syscalls.matches(syscall.number) {
+                    continue;
+                }
+                *count += 1;
+                if !injected && rule.triggers(*count) {
+                    // Skip the syscall, leaving our own return value
+                    let mut regs = regs;
+                    regs.set_return_value(rule.fault.return_value());
+                    tracer.regs.set_int(&regs)?;
+                    next_flags |= Flags::FLAG_IGNORE;
+                    injected = true;
                 }
             }
-        }
 
-        let handle = tracers.get_mut(&index).unwrap();
-        handle.tracer.next(crate::TRACE_FLAGS)?;
+            let traced = filter.trace.matches(syscall.number);
+            let tracks_fds = handle.fds.is_some() && FdTable::tracks(syscall.number);
+            if !traced && !tracks_fds {
+                handle.unclosed.push(None);
+                return Ok(next_flags);
+            }
+            if stats.is_none() {
+                syscall = regs.decode_syscall(Some(&mut tracer.mem), limit);
+            }
+            if traced && stats.is_none() {
+                if opt.count_instructions {
+                    sink.write(
+                        output.writer(Some(pid))?,
+                        &handle.record(opt, RecordKind::InstructionCount(instructions)),
+                    )?;
+                }
+                sink.write(
+                    output.writer(Some(pid))?,
+                    &handle.record(opt, RecordKind::SyscallEntry(&syscall)),
+                )?;
+                if opt.backtraces {
+                    let backtrace = Backtrace::walk(&regs, &mut tracer.mem, opt.backtrace_limit);
+                    sink.write(
+                        output.writer(Some(pid))?,
+                        &handle.record(opt, RecordKind::Backtrace(&backtrace)),
+                    )?;
+                }
+            }
+            if let Some(fds) = handle.fds.as_mut() {
+                // Only once the call was shown, since the descriptor
+                // it executes may be closed on exec itself
+                if syscall.number == syscall::SYS_FEXEC {
+                    fds.exec();
+                }
+            }
+            handle.unclosed.push(Some(Pending {
+                syscall,
+                start: Instant::now(),
+                traced,
+                injected,
+            }));
+        },
+        EventData::StopPostSyscall => {
+            let mut pending = match handle.unclosed.pop() {
+                Some(None) => return Ok(next_flags),
+                Some(Some(pending)) => Some(pending),
+                None => None,
+            };
+
+            let regs = tracer.regs.get_int()?;
+            let ret = regs.return_value();
+
+            if let Some(stats) = stats.as_deref_mut() {
+                if let Some(pending) = pending.filter(|pending| pending.traced) {
+                    let time = pending.start.elapsed();
+                    stats.record(pid, pending.syscall.number, ret, time);
+                }
+                return Ok(next_flags);
+            }
+
+            let duration = pending.as_ref().map(|pending| pending.start.elapsed());
+            if let Some(ref mut pending) = pending {
+                let fd = pending.syscall.raw.first().copied().unwrap_or(0);
+                let limit = buffer_limit(opt, pending.syscall.number, fd);
+                pending
+                    .syscall
+                    .decode_outputs(Some(&mut tracer.mem), ret, limit);
+            }
+            if !matches!(pending, Some(Pending { traced: false, .. })) {
+                let syscall = pending.as_ref().map(|pending| &pending.syscall);
+                let kind = RecordKind::SyscallExit {
+                    syscall,
+                    ret,
+                    duration,
+                    injected: matches!(pending, Some(Pending { injected: true, .. })),
+                };
+                sink.write(output.writer(Some(pid))?, &handle.record(opt, kind))?;
+            }
+            // Only forget about closed descriptors after the call was
+            // shown
+            if let (Some(fds), Some(pending)) = (handle.fds.as_mut(), pending.as_ref()) {
+                fds.update(&pending.syscall, ret);
+            }
+        },
+        EventData::StopSinglestep => {
+            handle.instructions += 1;
+            if !opt.instructions || stats.is_some() {
+                return Ok(next_flags);
+            }
+            let ip = tracer.regs.get_int()?.instruction_pointer();
+            if matches!(opt.instructions_range, Some(range) if !range.contains(ip)) {
+                return Ok(next_flags);
+            }
+            let bytes = tracer.mem.read_instruction(ip as *const u8)?;
+            sink.write(
+                output.writer(Some(pid))?,
+                &handle.record(opt, RecordKind::Instruction { ip, bytes: &bytes }),
+            )?;
+        },
+        _ => {
+            let reported = match event.data {
+                EventData::StopSignal(signal, _) => filter.signal.matches(signal),
+                _ => true,
+            };
+            if stats.is_none() && reported {
+                sink.write(
+                    output.writer(Some(pid))?,
+                    &handle.record(opt, RecordKind::Event(&event)),
+                )?;
+            }
+
+            match event.data {
+                // The session is already tracing the child
+                EventData::EventClone { pid: child, .. } if opt.recursive => {
+                    if let Some(tree) = tracees.tree.as_deref_mut() {
+                        tree.add_child(pid, child);
+                    }
+
+                    // The child starts out with a copy of the parent's
+                    // file descriptors and memory
+                    let fds = handle.fds.clone();
+                    let symbols = handle.symbols.clone();
+                    tracees
+                        .handles
+                        .insert(child, Handle::new(child, fds, symbols, opt.inject.len()));
+                },
+                EventData::StopExit(status) => {
+                    // The session lets go of the process once it's gone
+                    tracees.handles.remove(&pid);
+                    if let Some(tree) = tracees.tree.as_deref_mut() {
+                        tree.exit(pid, status);
+                    }
+                },
+                _ => (),
+            }
+        },
     }
+    Ok(next_flags)
 }
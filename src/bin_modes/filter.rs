@@ -0,0 +1,279 @@
+use std::{collections::HashSet, str::FromStr};
+
+use strace::{
+    signal::Signal,
+    syscalls::{self, SyscallClass},
+};
+
+/// A set of numbers selected on the command line by name, such as
+/// `open,fstat` or `!futex,yield`. A leading `!` selects everything
+/// but the listed names, and the names `all` and `none` select
+/// everything or nothing.
+#[derive(Clone, Debug)]
+struct NumberSet {
+    numbers: HashSet<usize>,
+    negated: bool,
+}
+impl NumberSet {
+    fn all() -> Self {
+        Self {
+            numbers: HashSet::new(),
+            negated: true,
+        }
+    }
+    fn matches(&self, number: usize) -> bool {
+        self.numbers.contains(&number) != self.negated
+    }
+    /// Parse a list of names. `all` is every number there is, and
+    /// `resolve` returns the numbers each other name stands for.
+    fn parse<I, F>(s: &str, all: I, mut resolve: F) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = usize>,
+        F: FnMut(&str) -> Result<Vec<usize>, String>,
+    {
+        let (negated, list) = match s.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+
+        let mut numbers = HashSet::new();
+        let mut all = Some(all);
+        for name in list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            match name {
+                "all" => numbers.extend(all.take().into_iter().flatten()),
+                "none" => (),
+                _ => numbers.extend(resolve(name)?),
+            }
+        }
+
+        Ok(Self { numbers, negated })
+    }
+}
+
+/// A set of syscalls selected on the command line, such as
+/// `open,fstat,%file` or `!futex,yield`.
+#[derive(Clone, Debug)]
+pub struct SyscallFilter(NumberSet);
+impl SyscallFilter {
+    /// A filter that lets every syscall through
+    pub fn all() -> Self {
+        Self(NumberSet::all())
+    }
+    /// Returns true if the syscall with this number should be traced
+    pub fn matches(&self, number: usize) -> bool {
+        self.0.matches(number)
+    }
+}
+impl FromStr for SyscallFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let all = syscalls::SYSCALLS.iter().map(|def| def.number);
+        NumberSet::parse(s, all, |name| match name.strip_prefix('%') {
+            Some(class) => {
+                let class = SyscallClass::from_name(class)
+                    .ok_or_else(|| format!("unknown syscall class: %{}", class))?;
+                Ok(syscalls::SYSCALLS
+                    .iter()
+                    .filter(|def| def.class.intersects(class))
+                    .map(|def| def.number)
+                    .collect())
+            },
+            None => {
+                let def = syscalls::lookup_name(name)
+                    .ok_or_else(|| format!("unknown syscall: {}", name))?;
+                Ok(vec![def.number])
+            },
+        })
+        .map(Self)
+    }
+}
+
+/// A set of signals selected on the command line, such as
+/// `SIGSEGV,CHLD` or `!SIGCHLD`.
+#[derive(Clone, Debug)]
+pub struct SignalFilter(NumberSet);
+impl SignalFilter {
+    /// A filter that lets every signal through
+    pub fn all() -> Self {
+        Self(NumberSet::all())
+    }
+    /// Returns true if the signal with this number should be reported
+    pub fn matches(&self, number: usize) -> bool {
+        self.0.matches(number)
+    }
+}
+impl FromStr for SignalFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let all = Signal::ALL.iter().map(|signal| signal.number());
+        NumberSet::parse(s, all, |name| {
+            if let Ok(number) = name.parse() {
+                return Ok(vec![number]);
+            }
+            let signal =
+                Signal::from_name(name).ok_or_else(|| format!("unknown signal: {}", name))?;
+            Ok(vec![signal.number()])
+        })
+        .map(Self)
+    }
+}
+
+/// A range of addresses given on the command line, such as
+/// `0x400000-0x401000`. The end is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressRange {
+    pub start: usize,
+    pub end: usize,
+}
+impl AddressRange {
+    pub fn contains(&self, address: usize) -> bool {
+        (self.start..self.end).contains(&address)
+    }
+}
+impl FromStr for AddressRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |address: &str| {
+            let address = address.trim();
+            let digits = address.strip_prefix("0x").unwrap_or(address);
+            usize::from_str_radix(digits, 16).map_err(|_| format!("invalid address: {}", address))
+        };
+        match s.find('-') {
+            Some(i) => Ok(Self {
+                start: parse(&s[..i])?,
+                end: parse(&s[i + 1..])?,
+            }),
+            None => Err(format!("expected START-END: {}", s)),
+        }
+    }
+}
+
+/// What gets traced, built from all `-e` expressions
+#[derive(Clone, Debug)]
+pub struct Filter {
+    pub trace: SyscallFilter,
+    pub signal: SignalFilter,
+}
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            trace: SyscallFilter::all(),
+            signal: SignalFilter::all(),
+        }
+    }
+}
+impl Filter {
+    pub fn new(exprs: &[Expr]) -> Self {
+        let mut filter = Self::default();
+        for expr in exprs {
+            match expr {
+                Expr::Trace(trace) => filter.trace = trace.clone(),
+                Expr::Signal(signal) => filter.signal = signal.clone(),
+            }
+        }
+        filter
+    }
+}
+
+/// One `-e` expression, in the form `qualifier=value`
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Trace(SyscallFilter),
+    Signal(SignalFilter),
+}
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (qualifier, value) = match s.find('=') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => ("trace", s),
+        };
+        match qualifier {
+            "trace" => value.parse().map(Expr::Trace),
+            "signal" => value.parse().map(Expr::Signal),
+            _ => Err(format!("unknown qualifier: {}", qualifier)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syscall::number::*;
+
+    #[test]
+    fn syscall_names() {
+        let filter: SyscallFilter = "open, close".parse().unwrap();
+        assert!(filter.matches(SYS_OPEN));
+        assert!(filter.matches(SYS_CLOSE));
+        assert!(!filter.matches(SYS_READ));
+    }
+    #[test]
+    fn syscall_negation() {
+        let filter: SyscallFilter = "!futex,getpid".parse().unwrap();
+        assert!(!filter.matches(SYS_FUTEX));
+        assert!(!filter.matches(SYS_GETPID));
+        assert!(filter.matches(SYS_READ));
+    }
+    #[test]
+    fn syscall_classes() {
+        let filter: SyscallFilter = "%file".parse().unwrap();
+        assert!(filter.matches(SYS_OPEN));
+        assert!(!filter.matches(SYS_READ));
+        assert!(!filter.matches(SYS_GETPID));
+
+        let filter: SyscallFilter = "%desc,getpid".parse().unwrap();
+        assert!(filter.matches(SYS_OPEN));
+        assert!(filter.matches(SYS_READ));
+        assert!(filter.matches(SYS_GETPID));
+        assert!(!filter.matches(SYS_FUTEX));
+    }
+    #[test]
+    fn syscall_all_and_none() {
+        let filter: SyscallFilter = "all".parse().unwrap();
+        assert!(filter.matches(SYS_FUTEX));
+        let filter: SyscallFilter = "none".parse().unwrap();
+        assert!(!filter.matches(SYS_FUTEX));
+        let filter: SyscallFilter = "!none".parse().unwrap();
+        assert!(filter.matches(SYS_FUTEX));
+    }
+    #[test]
+    fn syscall_unknown() {
+        assert_eq!(
+            "open,frobnicate".parse::<SyscallFilter>().unwrap_err(),
+            "unknown syscall: frobnicate"
+        );
+        assert_eq!(
+            "%bogus".parse::<SyscallFilter>().unwrap_err(),
+            "unknown syscall class: %bogus"
+        );
+    }
+    #[test]
+    fn signals() {
+        let filter: SignalFilter = "SIGSEGV, CHLD,1".parse().unwrap();
+        assert!(filter.matches(syscall::SIGSEGV));
+        assert!(filter.matches(syscall::SIGCHLD));
+        assert!(filter.matches(syscall::SIGHUP));
+        assert!(!filter.matches(syscall::SIGINT));
+
+        let filter: SignalFilter = "!SIGCHLD".parse().unwrap();
+        assert!(!filter.matches(syscall::SIGCHLD));
+        assert!(filter.matches(syscall::SIGINT));
+
+        let filter: SignalFilter = "all".parse().unwrap();
+        assert!(filter.matches(syscall::SIGINT));
+
+        assert_eq!(
+            "SIGBOGUS".parse::<SignalFilter>().unwrap_err(),
+            "unknown signal: SIGBOGUS"
+        );
+    }
+}
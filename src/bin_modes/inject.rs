@@ -0,0 +1,160 @@
+use std::str::FromStr;
+
+use strace::errno;
+
+use super::filter::SyscallFilter;
+
+/// What an injected syscall returns instead of running
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    Error(i32),
+    Retval(usize),
+}
+impl Fault {
+    /// The raw return value to place in the return register
+    pub fn return_value(self) -> usize {
+        match self {
+            Fault::Error(errno) => syscall::Error::mux(Err(syscall::Error::new(errno))),
+            Fault::Retval(value) => value,
+        }
+    }
+}
+
+/// One `--inject` rule, such as `open:error=ENOENT:when=2+3`
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub syscalls: SyscallFilter,
+    pub fault: Fault,
+    /// The first matching call to inject into, counting from 1
+    first: u64,
+    /// If set, inject into every `step`th matching call after `first`
+    step: Option<u64>,
+}
+impl Rule {
+    /// Returns true if the `nth` call matching this rule in a process,
+    /// counting from 1, should be injected into
+    pub fn triggers(&self, nth: u64) -> bool {
+        match self.step {
+            _ if nth < self.first => false,
+            Some(step) => (nth - self.first).checked_rem(step) == Some(0),
+            None => nth == self.first,
+        }
+    }
+}
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let syscalls = parts.next().unwrap_or("").parse()?;
+
+        let mut fault = None;
+        let mut first = 1;
+        let mut step = Some(1);
+        for part in parts {
+            let (key, value) = match part.find('=') {
+                Some(i) => (&part[..i], &part[i + 1..]),
+                None => return Err(format!("expected key=value: {}", part)),
+            };
+            match key {
+                "error" => {
+                    let errno = errno::from_name(value)
+                        .or_else(|| value.parse().ok())
+                        .ok_or_else(|| format!("unknown error: {}", value))?;
+                    fault = Some(Fault::Error(errno));
+                },
+                "retval" => {
+                    let value = value
+                        .parse()
+                        .map_err(|_| format!("invalid return value: {}", value))?;
+                    fault = Some(Fault::Retval(value));
+                },
+                "when" => {
+                    let invalid = || format!("invalid when: {}", value);
+                    let (n, rest) = match value.find('+') {
+                        Some(i) => (&value[..i], Some(&value[i + 1..])),
+                        None => (value, None),
+                    };
+                    first = n.parse().map_err(|_| invalid())?;
+                    step = match rest {
+                        None => None,
+                        Some("") => Some(1),
+                        Some(rest) => Some(rest.parse().map_err(|_| invalid())?),
+                    };
+                    if first == 0 || step == Some(0) {
+                        return Err(invalid());
+                    }
+                },
+                _ => return Err(format!("unknown inject option: {}", key)),
+            }
+        }
+
+        Ok(Self {
+            syscalls,
+            fault: fault.ok_or("expected error= or retval=")?,
+            first,
+            step,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syscall::number::*;
+
+    fn triggered(rule: &str) -> Vec<u64> {
+        let rule: Rule = rule.parse().unwrap();
+        (1..=10).filter(|&nth| rule.triggers(nth)).collect()
+    }
+
+    #[test]
+    fn parse() {
+        let rule: Rule = "open,close:error=ENOENT".parse().unwrap();
+        assert!(rule.syscalls.matches(SYS_OPEN));
+        assert!(rule.syscalls.matches(SYS_CLOSE));
+        assert!(!rule.syscalls.matches(SYS_READ));
+        assert_eq!(rule.fault, Fault::Error(syscall::ENOENT));
+
+        let rule: Rule = "read:error=5".parse().unwrap();
+        assert_eq!(rule.fault, Fault::Error(syscall::EIO));
+
+        let rule: Rule = "getpid:retval=42".parse().unwrap();
+        assert_eq!(rule.fault, Fault::Retval(42));
+        assert_eq!(rule.fault.return_value(), 42);
+    }
+    #[test]
+    fn parse_errors() {
+        let error = |rule: &str| rule.parse::<Rule>().unwrap_err();
+        assert_eq!(error("open"), "expected error= or retval=");
+        assert_eq!(error("open:error=EBOGUS"), "unknown error: EBOGUS");
+        assert_eq!(error("open:retval=-"), "invalid return value: -");
+        assert_eq!(error("open:error"), "expected key=value: error");
+        assert_eq!(
+            error("open:signal=SIGSEGV"),
+            "unknown inject option: signal"
+        );
+        assert_eq!(error("frobnicate:error=EIO"), "unknown syscall: frobnicate");
+        assert_eq!(error("open:error=EIO:when=0"), "invalid when: 0");
+        assert_eq!(error("open:error=EIO:when=1+0"), "invalid when: 1+0");
+        assert_eq!(error("open:error=EIO:when=x+"), "invalid when: x+");
+    }
+    #[test]
+    fn when() {
+        assert_eq!(triggered("open:error=EIO"), (1..=10).collect::<Vec<_>>());
+        assert_eq!(triggered("open:error=EIO:when=3"), [3]);
+        assert_eq!(
+            triggered("open:error=EIO:when=3+"),
+            [3, 4, 5, 6, 7, 8, 9, 10]
+        );
+        assert_eq!(triggered("open:error=EIO:when=2+3"), [2, 5, 8]);
+    }
+    #[test]
+    fn error_return_value() {
+        let fault = Fault::Error(syscall::ENOENT);
+        assert_eq!(
+            syscall::Error::demux(fault.return_value()).map_err(|err| err.errno),
+            Err(syscall::ENOENT)
+        );
+    }
+}
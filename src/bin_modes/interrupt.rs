@@ -0,0 +1,33 @@
+//! Stopping the trace cleanly on SIGINT and SIGTERM
+
+use std::{
+    io::Result,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use syscall::{data::SigAction, flag::SigActionFlags};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handler(_signal: usize) {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Catch SIGINT and SIGTERM. Once one arrives, blocking calls fail
+/// with `ErrorKind::Interrupted` and `requested` returns true.
+pub fn catch() -> Result<()> {
+    let action = SigAction {
+        sa_handler: Some(handler),
+        sa_mask: [0; 2],
+        sa_flags: SigActionFlags::empty(),
+    };
+    for &signal in &[syscall::SIGINT, syscall::SIGTERM] {
+        crate::e(syscall::sigaction(signal, Some(&action), None))?;
+    }
+    Ok(())
+}
+
+/// Returns true if the user asked strace to stop tracing
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
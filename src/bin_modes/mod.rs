@@ -7,3 +7,16 @@ pub use simple::*;
 mod advanced;
 #[cfg(feature = "advanced")]
 pub use advanced::*;
+
+#[cfg(feature = "advanced")]
+mod filter;
+#[cfg(feature = "advanced")]
+mod inject;
+
+mod interrupt;
+mod output;
+mod sink;
+mod stats;
+mod timestamp;
+#[cfg(feature = "advanced")]
+mod tree;
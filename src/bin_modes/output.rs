@@ -0,0 +1,74 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::File,
+    io::{self, prelude::*, LineWriter, Result},
+    path::PathBuf,
+};
+
+use strace::Pid;
+
+enum Target {
+    Stderr,
+    File(LineWriter<File>),
+    Separate {
+        prefix: PathBuf,
+        files: HashMap<Pid, LineWriter<File>>,
+    },
+}
+
+/// Where trace output goes: stderr by default, or the file given by
+/// `-o`, optionally split into one `FILE.<pid>` per process.
+pub struct Output {
+    target: Target,
+    stderr: io::Stderr,
+}
+impl Output {
+    /// Write all output to stderr
+    pub fn stderr() -> Self {
+        Self {
+            target: Target::Stderr,
+            stderr: io::stderr(),
+        }
+    }
+    /// Write output to `path`, or to `path.<pid>` for each process if
+    /// `separately` is set
+    pub fn create(path: PathBuf, separately: bool) -> Result<Self> {
+        let target = if separately {
+            Target::Separate {
+                prefix: path,
+                files: HashMap::new(),
+            }
+        } else {
+            Target::File(LineWriter::new(File::create(path)?))
+        };
+        Ok(Self {
+            target,
+            stderr: io::stderr(),
+        })
+    }
+    /// Returns the writer for output about the specified process, or
+    /// for output not tied to any process if `pid` is `None`.
+    pub fn writer(&mut self, pid: Option<Pid>) -> Result<&mut dyn Write> {
+        match self.target {
+            Target::Stderr => Ok(&mut self.stderr),
+            Target::File(ref mut file) => Ok(file),
+            Target::Separate {
+                ref prefix,
+                ref mut files,
+            } => {
+                let pid = match pid {
+                    Some(pid) => pid,
+                    None => return Ok(&mut self.stderr),
+                };
+                match files.entry(pid) {
+                    Entry::Occupied(entry) => Ok(entry.into_mut()),
+                    Entry::Vacant(entry) => {
+                        let mut path = prefix.clone().into_os_string();
+                        path.push(format!(".{}", pid));
+                        Ok(entry.insert(LineWriter::new(File::create(path)?)))
+                    },
+                }
+            },
+        }
+    }
+}
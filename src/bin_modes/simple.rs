@@ -1,55 +1,194 @@
-use std::{env, io::Result, process};
+use std::{
+    env,
+    io::{ErrorKind, Result},
+    path::{Path, PathBuf},
+    process,
+    time::Instant,
+};
 
-use strace::{Flags, Pid, Tracer};
+use strace::{
+    syscalls::{DecodedSyscall, RenderOptions, DEFAULT_STRING_LIMIT},
+    EventData, Pid, Tracer,
+};
+
+use super::{
+    interrupt,
+    output::Output,
+    sink::{Format, JsonSink, Record, RecordKind, Sink, TextSink},
+    stats::Stats,
+    timestamp::Clock,
+};
 
 pub struct Opt {
+    pub summary: bool,
+    pub output: Option<PathBuf>,
+    pub format: Format,
     pub cmd: Vec<String>,
 }
 
+fn usage() -> ! {
+    eprintln!("Usage: strace [-c] [-o FILE] [--format text|json] <path>");
+    process::exit(1);
+}
+
 pub fn parse_args() -> Opt {
-    let cmd: Vec<_> = env::args().skip(1).collect();
-    if cmd.is_empty() {
-        eprintln!("Usage: strace <path>");
-        process::exit(1);
+    let mut opt = Opt {
+        summary: false,
+        output: None,
+        format: Format::Text,
+        cmd: Vec::new(),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "-c" => opt.summary = true,
+            "-o" => opt.output = Some(args.next().unwrap_or_else(|| usage()).into()),
+            "--format" => {
+                let format = args.next().unwrap_or_else(|| usage());
+                opt.format = format.parse().unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    usage()
+                });
+            },
+            _ => {
+                opt.cmd.push(arg);
+                opt.cmd.extend(&mut args);
+            },
+        }
+    }
+    if opt.cmd.is_empty() {
+        usage();
     }
-    Opt { cmd }
+    opt
 }
 
-pub fn inner_main(_pid: Pid, mut tracer: Tracer, _opt: Opt) -> Result<()> {
+struct Pending {
+    syscall: DecodedSyscall,
+    start: Instant,
+}
+
+pub fn inner_main(pid: Pid, _program: &Path, tracer: Tracer, opt: Opt) -> Result<()> {
+    let mut output = match opt.output {
+        Some(path) => Output::create(path, false)?,
+        None => Output::stderr(),
+    };
+    let mut sink: Box<dyn Sink> = match opt.format {
+        Format::Text => Box::new(TextSink {
+            clock: Clock::new(0, false),
+            syscall_times: false,
+            show_pid: false,
+            render: RenderOptions {
+                string_limit: Some(DEFAULT_STRING_LIMIT),
+                hex: false,
+                fds: None,
+            },
+            dump_io: Vec::new(),
+        }),
+        Format::Json => Box::new(JsonSink),
+    };
+    let mut stats = if opt.summary {
+        Some(Stats::new(false))
+    } else {
+        None
+    };
+
+    interrupt::catch()?;
+    let result = trace(pid, tracer, &mut output, &mut *sink, stats.as_mut());
+
+    if let Some(stats) = stats {
+        stats.print(output.writer(None)?)?;
+    }
+    result
+}
+
+fn trace(
+    pid: Pid,
+    mut tracer: Tracer,
+    output: &mut Output,
+    sink: &mut dyn Sink,
+    mut stats: Option<&mut Stats>,
+) -> Result<()> {
     let mut unclosed = Vec::new();
 
     loop {
-        let event =
-            tracer
-                .next_event(crate::TRACE_FLAGS)?
-                .from_callback(|event| -> Result<()> {
-                    eprintln!("EVENT: {:?}", event);
-                    Ok(())
-                })?;
-
-        if event.cause == Flags::STOP_PRE_SYSCALL {
-            let regs = tracer.regs.get_int()?;
-            let syscall = regs.format_syscall_full(&mut tracer.mem);
-
-            eprintln!("SYSCALL:     {}", syscall);
-            unclosed.push(syscall);
-        } else if event.cause == Flags::STOP_POST_SYSCALL {
-            let syscall = unclosed.pop();
-            let syscall = syscall
-                .as_ref()
-                .map(|s| &**s)
-                .unwrap_or("<unmatched syscall>");
-
-            let regs = tracer.regs.get_int()?;
-            let ret = regs.return_value();
-
-            eprint!("SYSCALL RET: {} = ", syscall);
-            match syscall::Error::demux(ret) {
-                Ok(val) => eprintln!("Ok({} ({:#X}))", val, val),
-                Err(err) => eprintln!("Err(\"{}\" ({:#X})) ({:#X})", err, err.errno, ret),
-            }
-        } else {
-            eprintln!("OTHER EVENT: {:?}", event);
+        let event = tracer.next_event(crate::TRACE_FLAGS).and_then(|handler| {
+            handler.from_callback(|event| -> Result<()> {
+                sink.write(
+                    output.writer(Some(pid))?,
+                    &Record::now(pid, RecordKind::Event(&event)),
+                )
+            })
+        });
+        let event = match event {
+            Err(ref err) if err.kind() == ErrorKind::Interrupted && interrupt::requested() => {
+                eprintln!("Detaching from PID {}", pid);
+                return tracer.detach();
+            },
+            event => event?,
+        };
+
+        match event.data {
+            EventData::StopPreSyscall => {
+                let regs = tracer.regs.get_int()?;
+                let syscall = if stats.is_none() {
+                    let syscall = regs.decode_syscall(Some(&mut tracer.mem), DEFAULT_STRING_LIMIT);
+                    sink.write(
+                        output.writer(Some(pid))?,
+                        &Record::now(pid, RecordKind::SyscallEntry(&syscall)),
+                    )?;
+                    syscall
+                } else {
+                    regs.decode_syscall(None, 0)
+                };
+
+                unclosed.push(Pending {
+                    syscall,
+                    start: Instant::now(),
+                });
+            },
+            EventData::StopPostSyscall => {
+                let mut pending = unclosed.pop();
+
+                let regs = tracer.regs.get_int()?;
+                let ret = regs.return_value();
+
+                if let Some(stats) = stats.as_deref_mut() {
+                    if let Some(pending) = pending {
+                        let time = pending.start.elapsed();
+                        stats.record(pid, pending.syscall.number, ret, time);
+                    }
+                    continue;
+                }
+
+                let duration = pending.as_ref().map(|pending| pending.start.elapsed());
+                if let Some(ref mut pending) = pending {
+                    pending.syscall.decode_outputs(
+                        Some(&mut tracer.mem),
+                        ret,
+                        DEFAULT_STRING_LIMIT,
+                    );
+                }
+                let syscall = pending.as_ref().map(|pending| &pending.syscall);
+                sink.write(
+                    output.writer(Some(pid))?,
+                    &Record::now(
+                        pid,
+                        RecordKind::SyscallExit {
+                            syscall,
+                            ret,
+                            duration,
+                            injected: false,
+                        },
+                    ),
+                )?;
+            },
+            _ if stats.is_none() => {
+                sink.write(
+                    output.writer(Some(pid))?,
+                    &Record::now(pid, RecordKind::Event(&event)),
+                )?;
+            },
+            _ => (),
         }
     }
 }
@@ -0,0 +1,557 @@
+use std::{
+    fmt::Write as _,
+    io::{prelude::*, Result},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use strace::{
+    backtrace::{Backtrace, End},
+    elf::Symbols,
+    errno::{self, SyscallResult},
+    fds::FdTable,
+    signal::{SigSet, Signal, SignalNumber},
+    syscalls::{Arg, DecodedSyscall, RenderOptions},
+    Event, EventData, Pid,
+};
+
+use super::timestamp::{self, Clock};
+
+/// Something that happened to a traced process
+pub struct Record<'a> {
+    pub pid: Pid,
+    pub time: SystemTime,
+    pub kind: RecordKind<'a>,
+    /// The file descriptors of the process, if they are tracked
+    pub fds: Option<&'a FdTable>,
+    /// The symbols of the program the process is running, if loaded
+    pub symbols: Option<&'a Symbols>,
+}
+impl<'a> Record<'a> {
+    /// Create a record of something that happened just now
+    pub fn now(pid: Pid, kind: RecordKind<'a>) -> Self {
+        Self {
+            pid,
+            time: SystemTime::now(),
+            kind,
+            fds: None,
+            symbols: None,
+        }
+    }
+}
+
+// Instructions are only traced in advanced mode
+#[cfg_attr(not(feature = "advanced"), allow(dead_code))]
+pub enum RecordKind<'a> {
+    SyscallEntry(&'a DecodedSyscall),
+    SyscallExit {
+        /// The syscall as decoded at entry, if it was seen
+        syscall: Option<&'a DecodedSyscall>,
+        ret: usize,
+        /// Time spent between the pre- and post-syscall stops
+        duration: Option<Duration>,
+        /// Whether the syscall was skipped and its result made up by
+        /// `--inject`
+        injected: bool,
+    },
+    /// The process is about to execute the instruction at `ip`
+    Instruction {
+        ip: usize,
+        bytes: &'a [u8],
+    },
+    /// The number of instructions executed since the previous syscall
+    InstructionCount(u64),
+    /// The call stack at the entry of the previous syscall
+    Backtrace(&'a Backtrace),
+    Event(&'a Event),
+}
+
+/// A format trace records can be written in
+pub trait Sink {
+    fn write(&mut self, out: &mut dyn Write, record: &Record) -> Result<()>;
+}
+
+/// The human readable format
+pub struct TextSink {
+    pub clock: Clock,
+    /// Show the time spent in each syscall
+    pub syscall_times: bool,
+    /// Label each line with the pid it belongs to
+    pub show_pid: bool,
+    pub render: RenderOptions<'static>,
+    /// File descriptors whose reads and writes get a full hex dump
+    pub dump_io: Vec<usize>,
+}
+impl TextSink {
+    /// The data transferred by a successful read or write on one of
+    /// the descriptors in `dump_io`
+    fn io_data<'a>(&self, syscall: &'a DecodedSyscall, ret: usize) -> Option<&'a [u8]> {
+        if syscall.number != syscall::SYS_READ && syscall.number != syscall::SYS_WRITE {
+            return None;
+        }
+        let len = syscall::Error::demux(ret).ok()?;
+        match (syscall.args.first(), syscall.args.get(1)) {
+            (Some(Arg::Fd(fd)), Some(Arg::Bytes(buf))) if self.dump_io.contains(fd) => {
+                let data = buf.data.as_ref()?;
+                Some(&data[..len.min(data.len())])
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Format a code address, followed by the symbol it belongs to if
+/// known, such as `0x401020 <main+0x10>`
+fn code_address(address: usize, symbols: Option<&Symbols>) -> String {
+    match symbols {
+        Some(symbols) if symbols.lookup(address).is_some() => {
+            format!("{:#X} <{}>", address, symbols.resolve(address))
+        },
+        _ => format!("{:#X}", address),
+    }
+}
+
+/// Write a hex and ASCII dump of `data`, 16 bytes per line
+fn hexdump(out: &mut dyn Write, data: &[u8]) -> Result<()> {
+    for (i, chunk) in data.chunks(16).enumerate() {
+        let mut line = format!(" | {:05x} ", i * 16);
+        for j in 0..16 {
+            match chunk.get(j) {
+                Some(byte) => {
+                    let _ = write!(line, " {:02x}", byte);
+                },
+                None => line.push_str("   "),
+            }
+        }
+        line.push_str("  ");
+        for &byte in chunk {
+            line.push(if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            });
+        }
+        for _ in chunk.len()..16 {
+            line.push(' ');
+        }
+        writeln!(out, "{} |", line)?;
+    }
+    Ok(())
+}
+impl Sink for TextSink {
+    fn write(&mut self, out: &mut dyn Write, record: &Record) -> Result<()> {
+        let prefix = self.clock.prefix();
+        let render = RenderOptions {
+            fds: record.fds,
+            ..self.render
+        };
+        let pid = if self.show_pid {
+            format!(" (pid {})", record.pid)
+        } else {
+            String::new()
+        };
+
+        match record.kind {
+            RecordKind::SyscallEntry(syscall) => {
+                if self.show_pid {
+                    writeln!(
+                        out,
+                        "{}SYSCALL    {}: {}",
+                        prefix,
+                        pid,
+                        syscall.display(render)
+                    )
+                } else {
+                    writeln!(out, "{}SYSCALL:     {}", prefix, syscall.display(render))
+                }
+            },
+            RecordKind::SyscallExit {
+                syscall,
+                ret,
+                duration,
+                injected,
+            } => {
+                let dump = syscall.and_then(|syscall| self.io_data(syscall, ret));
+                let syscall = syscall
+                    .map(|syscall| syscall.display(render).to_string())
+                    .unwrap_or_else(|| String::from("<unmatched syscall>"));
+                let ret = SyscallResult::from_raw(ret);
+                let duration = match duration {
+                    Some(duration) if self.syscall_times => {
+                        format!(" <{}>", timestamp::format_duration(duration))
+                    },
+                    _ => String::new(),
+                };
+                let injected = if injected { " (INJECTED)" } else { "" };
+                writeln!(
+                    out,
+                    "{}SYSCALL RET{}: {} = {}{}{}",
+                    prefix, pid, syscall, ret, injected, duration
+                )?;
+                if let Some(data) = dump {
+                    hexdump(out, data)?;
+                }
+                Ok(())
+            },
+            RecordKind::Instruction { ip, bytes } => {
+                let mut hex = String::new();
+                for byte in bytes {
+                    let _ = write!(hex, " {:02x}", byte);
+                }
+                let ip = code_address(ip, record.symbols);
+                if self.show_pid {
+                    writeln!(out, "{}STEP       {}: {}:{}", prefix, pid, ip, hex)
+                } else {
+                    writeln!(out, "{}STEP:        {}:{}", prefix, ip, hex)
+                }
+            },
+            RecordKind::InstructionCount(count) => {
+                if self.show_pid {
+                    writeln!(
+                        out,
+                        "{}STEPS      {}: {} instructions since the last syscall",
+                        prefix, pid, count
+                    )
+                } else {
+                    writeln!(
+                        out,
+                        "{}STEPS:       {} instructions since the last syscall",
+                        prefix, count
+                    )
+                }
+            },
+            RecordKind::Backtrace(backtrace) => {
+                for &address in &backtrace.frames {
+                    writeln!(out, " > {}", code_address(address, record.symbols))?;
+                }
+                match backtrace.end {
+                    End::Complete => Ok(()),
+                    End::Limit => writeln!(out, " > ..."),
+                    End::Unreadable(frame) => {
+                        writeln!(out, " > ... (unreadable frame at {:#X})", frame)
+                    },
+                    End::Corrupt(frame) => writeln!(out, " > ... (bad frame pointer {:#X})", frame),
+                }
+            },
+            RecordKind::Event(Event {
+                data: EventData::StopSignal(signal, handler),
+                ..
+            }) => {
+                let signal = SignalNumber(*signal);
+                let handler = code_address(*handler, record.symbols);
+                if self.show_pid {
+                    writeln!(
+                        out,
+                        "{}SIGNAL     {}: {} (handler {})",
+                        prefix, pid, signal, handler
+                    )
+                } else {
+                    writeln!(
+                        out,
+                        "{}SIGNAL:      {} (handler {})",
+                        prefix, signal, handler
+                    )
+                }
+            },
+            RecordKind::Event(event) => writeln!(out, "{}OTHER EVENT: {:?}", prefix, event),
+        }
+    }
+}
+
+/// One JSON object per line, for consumption by scripts
+pub struct JsonSink;
+
+fn json_str(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            },
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+fn json_list<T, F>(items: &[T], f: F) -> String
+where
+    F: Fn(&T) -> String,
+{
+    let items: Vec<_> = items.iter().map(f).collect();
+    format!("[{}]", items.join(","))
+}
+fn json_opt<T, F>(value: Option<&T>, f: F) -> String
+where
+    F: FnOnce(&T) -> String,
+{
+    value.map(f).unwrap_or_else(|| String::from("null"))
+}
+fn json_symbol(address: usize, symbols: Option<&Symbols>) -> String {
+    match symbols {
+        Some(symbols) if symbols.lookup(address).is_some() => {
+            json_str(&symbols.resolve(address).to_string())
+        },
+        _ => String::from("null"),
+    }
+}
+fn json_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(data.len() * 2);
+    for byte in data {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+fn json_secs(duration: Duration) -> String {
+    timestamp::format_duration(duration)
+}
+
+fn json_arg(arg: &Arg) -> String {
+    match arg {
+        Arg::Int(n) | Arg::Hex(n) | Arg::Mode(n) | Arg::Fd(n) => n.to_string(),
+        Arg::Signed(n) => n.to_string(),
+        Arg::Path(buf) => json_opt(buf.data.as_ref(), |data| {
+            json_str(&String::from_utf8_lossy(data))
+        }),
+        // Buffers may hold anything, so they're given as exact hex
+        Arg::Bytes(buf) => format!(
+            "{{\"len\":{},\"truncated\":{},\"hex\":{}}}",
+            buf.len,
+            buf.truncated,
+            json_opt(buf.data.as_ref(), |data| json_str(&json_hex(data)))
+        ),
+        Arg::OpenFlags(_)
+        | Arg::CloneFlags(_)
+        | Arg::MapFlags(_)
+        | Arg::PhysmapFlags(_)
+        | Arg::WaitFlags(_)
+        | Arg::Whence(_)
+        | Arg::FcntlCmd(_)
+        | Arg::Signal(_)
+        | Arg::SigHow(_) => json_str(&arg.to_string()),
+        Arg::Maps(ptr) => json_opt(ptr.value.as_ref(), |maps| {
+            json_list(maps, |map| {
+                format!(
+                    "{{\"offset\":{},\"size\":{},\"flags\":{}}}",
+                    map.offset,
+                    map.size,
+                    json_str(&format!("{:?}", map.flags))
+                )
+            })
+        }),
+        Arg::Stat(ptr) => json_opt(ptr.value.as_ref(), |stat| {
+            format!(
+                "{{\"st_dev\":{},\"st_ino\":{},\"st_mode\":{},\"st_nlink\":{},\"st_uid\":{},\
+                 \"st_gid\":{},\"st_size\":{},\"st_blksize\":{},\"st_blocks\":{},\
+                 \"st_mtime\":{},\"st_mtime_nsec\":{},\"st_atime\":{},\"st_atime_nsec\":{},\
+                 \"st_ctime\":{},\"st_ctime_nsec\":{}}}",
+                stat.st_dev,
+                stat.st_ino,
+                stat.st_mode,
+                stat.st_nlink,
+                stat.st_uid,
+                stat.st_gid,
+                stat.st_size,
+                stat.st_blksize,
+                stat.st_blocks,
+                stat.st_mtime,
+                stat.st_mtime_nsec,
+                stat.st_atime,
+                stat.st_atime_nsec,
+                stat.st_ctime,
+                stat.st_ctime_nsec
+            )
+        }),
+        Arg::TimeSpec(ptr) => json_opt(ptr.value.as_ref(), |time| {
+            format!(
+                "{{\"tv_sec\":{},\"tv_nsec\":{}}}",
+                time.tv_sec, time.tv_nsec
+            )
+        }),
+        Arg::TimeSpecs(ptr) => json_opt(ptr.value.as_ref(), |times| {
+            json_list(times, |time| {
+                format!(
+                    "{{\"tv_sec\":{},\"tv_nsec\":{}}}",
+                    time.tv_sec, time.tv_nsec
+                )
+            })
+        }),
+        Arg::Futex(ptr) => json_opt(ptr.value.as_ref(), |value| value.to_string()),
+        Arg::FdPair(ptr) => json_opt(ptr.value.as_ref(), |fds| {
+            json_list(fds, |fd| fd.to_string())
+        }),
+        Arg::StrList(ptr) => json_opt(ptr.value.as_ref(), |list| {
+            json_list(list, |s| json_opt(s.as_ref(), |s| json_str(s)))
+        }),
+        Arg::SigSet(ptr) => json_opt(ptr.value.as_ref(), |set| json_sigset(*set)),
+        Arg::SigAction(ptr) => json_opt(ptr.value.as_ref(), |action| {
+            format!(
+                "{{\"handler\":{},\"mask\":{},\"flags\":{}}}",
+                action.sa_handler.map_or(0, |handler| handler as usize),
+                json_sigset(SigSet(action.sa_mask)),
+                json_str(&format!("{:?}", action.sa_flags))
+            )
+        }),
+    }
+}
+fn json_sigset(set: SigSet) -> String {
+    let names: Vec<_> = set
+        .numbers()
+        .map(|number| json_str(&SignalNumber(number).to_string()))
+        .collect();
+    format!("[{}]", names.join(","))
+}
+
+fn json_syscall(syscall: &DecodedSyscall) -> String {
+    format!(
+        "\"number\":{},\"syscall\":{},\"raw_args\":{},\"args\":{}",
+        syscall.number,
+        json_opt(syscall.name.as_ref(), |name| json_str(name)),
+        json_list(&syscall.raw, |reg| reg.to_string()),
+        json_list(&syscall.args, json_arg)
+    )
+}
+
+impl Sink for JsonSink {
+    fn write(&mut self, out: &mut dyn Write, record: &Record) -> Result<()> {
+        let time = record.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let fields = match record.kind {
+            RecordKind::SyscallEntry(syscall) => {
+                format!("\"event\":\"syscall_entry\",{}", json_syscall(syscall))
+            },
+            RecordKind::SyscallExit {
+                syscall,
+                ret,
+                duration,
+                injected,
+            } => {
+                let (value, errno, error) = match syscall::Error::demux(ret) {
+                    Ok(value) => (
+                        value.to_string(),
+                        String::from("null"),
+                        String::from("null"),
+                    ),
+                    Err(err) => (
+                        String::from("null"),
+                        err.errno.to_string(),
+                        json_opt(errno::name(err.errno).as_ref(), |name| json_str(name)),
+                    ),
+                };
+                let mut fields = String::from("\"event\":\"syscall_exit\",");
+                if let Some(syscall) = syscall {
+                    fields.push_str(&json_syscall(syscall));
+                    fields.push(',');
+                }
+                let _ = write!(
+                    fields,
+                    "\"ret\":{},\"value\":{},\"errno\":{},\"error\":{},\"duration\":{},\
+                     \"injected\":{}",
+                    ret,
+                    value,
+                    errno,
+                    error,
+                    json_opt(duration.as_ref(), |duration| json_secs(*duration)),
+                    injected
+                );
+                fields
+            },
+            RecordKind::Instruction { ip, bytes } => format!(
+                "\"event\":\"instruction\",\"ip\":{},\"symbol\":{},\"bytes\":{}",
+                ip,
+                json_symbol(ip, record.symbols),
+                json_list(bytes, |byte| byte.to_string())
+            ),
+            RecordKind::InstructionCount(count) => {
+                format!("\"event\":\"instruction_count\",\"count\":{}", count)
+            },
+            RecordKind::Backtrace(backtrace) => {
+                let (end, frame) = match backtrace.end {
+                    End::Complete => ("complete", None),
+                    End::Limit => ("limit", None),
+                    End::Unreadable(frame) => ("unreadable", Some(frame)),
+                    End::Corrupt(frame) => ("corrupt", Some(frame)),
+                };
+                format!(
+                    "\"event\":\"backtrace\",\"frames\":{},\"end\":{},\"bad_frame\":{}",
+                    json_list(&backtrace.frames, |&address| format!(
+                        "{{\"address\":{},\"symbol\":{}}}",
+                        address,
+                        json_symbol(address, record.symbols)
+                    )),
+                    json_str(end),
+                    json_opt(frame.as_ref(), |frame| frame.to_string())
+                )
+            },
+            RecordKind::Event(event) => match event.data {
+                EventData::EventClone { pid, flags } => format!(
+                    "\"event\":\"clone\",\"child\":{},\"flags\":{}",
+                    pid,
+                    json_str(&format!("{:?}", flags))
+                ),
+                EventData::StopPreSyscall => String::from("\"event\":\"pre_syscall\""),
+                EventData::StopPostSyscall => String::from("\"event\":\"post_syscall\""),
+                EventData::StopSinglestep => String::from("\"event\":\"singlestep\""),
+                EventData::StopBreakpoint => String::from("\"event\":\"breakpoint\""),
+                EventData::StopSignal(signal, handler) => format!(
+                    "\"event\":\"signal\",\"signal\":{},\"name\":{},\"handler\":{},\
+                     \"handler_symbol\":{}",
+                    signal,
+                    json_opt(Signal::from_number(signal).as_ref(), |signal| json_str(
+                        signal.name()
+                    )),
+                    handler,
+                    json_symbol(handler, record.symbols)
+                ),
+                EventData::StopExit(status) => format!(
+                    "\"event\":\"exit\",\"status\":{},\"code\":{},\"signal\":{}",
+                    status.0,
+                    json_opt(status.code().as_ref(), |code| code.to_string()),
+                    json_opt(status.signal().as_ref(), |signal| json_str(
+                        &SignalNumber(*signal).to_string()
+                    ))
+                ),
+                EventData::Unknown(a, b, c, d, e, f) => format!(
+                    "\"event\":\"unknown\",\"cause\":{},\"data\":[{},{},{},{},{},{}]",
+                    event.cause.bits(),
+                    a,
+                    b,
+                    c,
+                    d,
+                    e,
+                    f
+                ),
+            },
+        };
+        writeln!(
+            out,
+            "{{\"pid\":{},\"time\":{},{}}}",
+            record.pid,
+            json_secs(time),
+            fields
+        )
+    }
+}
+
+/// The sink selected with `--format`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{prelude::*, Result},
+    time::Duration,
+};
+
+use strace::{syscalls, Pid};
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Counter {
+    calls: u64,
+    errors: u64,
+    time: Duration,
+}
+
+/// Per-syscall counts, errors and time spent, collected for `-c`
+#[derive(Debug, Default)]
+pub struct Stats {
+    per_pid: bool,
+    counters: BTreeMap<Pid, HashMap<usize, Counter>>,
+}
+impl Stats {
+    /// Create new statistics. If `per_pid` is set, the summary will
+    /// include a separate table for each process.
+    pub fn new(per_pid: bool) -> Self {
+        Self {
+            per_pid,
+            counters: BTreeMap::new(),
+        }
+    }
+    /// Record one finished syscall with its return value and the time
+    /// between its pre- and post-syscall stops
+    pub fn record(&mut self, pid: Pid, number: usize, ret: usize, time: Duration) {
+        let counter = self
+            .counters
+            .entry(pid)
+            .or_default()
+            .entry(number)
+            .or_default();
+        counter.calls += 1;
+        if syscall::Error::demux(ret).is_err() {
+            counter.errors += 1;
+        }
+        counter.time += time;
+    }
+    /// Print the summary tables
+    pub fn print(&self, out: &mut dyn Write) -> Result<()> {
+        if self.per_pid && self.counters.len() > 1 {
+            for (pid, counters) in &self.counters {
+                writeln!(out, "pid {}:", pid)?;
+                print_table(out, counters)?;
+                writeln!(out)?;
+            }
+        }
+
+        let mut total = HashMap::<usize, Counter>::new();
+        for counters in self.counters.values() {
+            for (&number, counter) in counters {
+                let sum = total.entry(number).or_default();
+                sum.calls += counter.calls;
+                sum.errors += counter.errors;
+                sum.time += counter.time;
+            }
+        }
+        print_table(out, &total)
+    }
+}
+
+fn print_table(out: &mut dyn Write, counters: &HashMap<usize, Counter>) -> Result<()> {
+    let mut rows: Vec<_> = counters
+        .iter()
+        .map(|(&number, counter)| {
+            let name = syscalls::lookup(number)
+                .map(|def| def.name.to_string())
+                .unwrap_or_else(|| format!("UNKNOWN{}", number));
+            (name, *counter)
+        })
+        .collect();
+    rows.sort_by(|(a_name, a), (b_name, b)| {
+        b.time
+            .cmp(&a.time)
+            .then(b.calls.cmp(&a.calls))
+            .then(a_name.cmp(b_name))
+    });
+
+    let total_time: Duration = rows.iter().map(|(_, counter)| counter.time).sum();
+    let total_calls: u64 = rows.iter().map(|(_, counter)| counter.calls).sum();
+    let total_errors: u64 = rows.iter().map(|(_, counter)| counter.errors).sum();
+    let separator = "------ ----------- ----------- --------- --------- ----------------";
+
+    writeln!(
+        out,
+        "{:>6} {:>11} {:>11} {:>9} {:>9} syscall",
+        "% time", "seconds", "usecs/call", "calls", "errors"
+    )?;
+    writeln!(out, "{}", separator)?;
+    for (name, counter) in &rows {
+        let percent = if total_time.as_nanos() == 0 {
+            0.0
+        } else {
+            counter.time.as_secs_f64() * 100.0 / total_time.as_secs_f64()
+        };
+        writeln!(
+            out,
+            "{:>6.2} {:>11.6} {:>11} {:>9} {:>9} {}",
+            percent,
+            counter.time.as_secs_f64(),
+            counter.time.as_micros() / u128::from(counter.calls),
+            counter.calls,
+            counter.errors,
+            name
+        )?;
+    }
+    writeln!(out, "{}", separator)?;
+    writeln!(
+        out,
+        "{:>6.2} {:>11.6} {:>11} {:>9} {:>9} total",
+        100.0,
+        total_time.as_secs_f64(),
+        "",
+        total_calls,
+        total_errors
+    )
+}
@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Prefixes trace lines with the time selected by `-t`, `-tt` or
+/// `-R`.
+#[derive(Debug)]
+pub struct Clock {
+    absolute: u8,
+    relative: bool,
+    last: Option<Instant>,
+}
+impl Clock {
+    /// Create a new clock. `absolute` is the number of times `-t` was
+    /// given: once for seconds, twice for microseconds.
+    pub fn new(absolute: u8, relative: bool) -> Self {
+        Self {
+            absolute,
+            relative,
+            last: None,
+        }
+    }
+    /// Returns the prefix for a new line, including a trailing space
+    /// if non-empty.
+    pub fn prefix(&mut self) -> String {
+        if self.relative {
+            let now = Instant::now();
+            let elapsed = self
+                .last
+                .map(|last| now.duration_since(last))
+                .unwrap_or_default();
+            self.last = Some(now);
+            return format!("{:>13} ", format_duration(elapsed));
+        }
+
+        let since_epoch = match self.absolute {
+            0 => return String::new(),
+            _ => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+        };
+        let secs = since_epoch.as_secs() % (24 * 60 * 60);
+        let time = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+        if self.absolute >= 2 {
+            format!("{}.{:06} ", time, since_epoch.subsec_micros())
+        } else {
+            format!("{} ", time)
+        }
+    }
+}
+
+/// Format a duration as seconds with microsecond precision
+pub fn format_duration(duration: Duration) -> String {
+    format!("{}.{:06}", duration.as_secs(), duration.subsec_micros())
+}
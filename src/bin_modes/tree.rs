@@ -0,0 +1,128 @@
+use std::{
+    collections::BTreeMap,
+    io::{prelude::*, Result},
+    time::Instant,
+};
+
+use strace::{
+    syscalls::{Arg, DecodedSyscall},
+    ExitStatus, Pid,
+};
+
+use super::timestamp;
+
+#[derive(Debug)]
+struct Process {
+    children: Vec<Pid>,
+    /// The arguments of the last program executed
+    program: Option<String>,
+    start: Instant,
+    end: Option<Instant>,
+    status: Option<ExitStatus>,
+}
+impl Process {
+    fn new() -> Self {
+        Self {
+            children: Vec::new(),
+            program: None,
+            start: Instant::now(),
+            end: None,
+            status: None,
+        }
+    }
+}
+
+/// The parent/child relationships of all traced processes, collected
+/// for `--tree`
+#[derive(Debug, Default)]
+pub struct ProcessTree {
+    roots: Vec<Pid>,
+    processes: BTreeMap<Pid, Process>,
+}
+impl ProcessTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Record a process that was traced from the start or attached to
+    pub fn add_root(&mut self, pid: Pid) {
+        self.roots.push(pid);
+        self.processes.insert(pid, Process::new());
+    }
+    /// Record a process being cloned from a traced parent
+    pub fn add_child(&mut self, parent: Pid, child: Pid) {
+        self.processes.insert(child, Process::new());
+        match self.processes.get_mut(&parent) {
+            Some(process) => process.children.push(child),
+            None => self.roots.push(child),
+        }
+    }
+    /// Record a process about to execute a new program. Pass the
+    /// `fexec` call decoded with memory available.
+    pub fn exec(&mut self, pid: Pid, fexec: &DecodedSyscall) {
+        let args = match fexec.args.get(1) {
+            Some(Arg::StrList(list)) => match list.value {
+                Some(ref args) => args,
+                None => return,
+            },
+            _ => return,
+        };
+        if let Some(process) = self.processes.get_mut(&pid) {
+            let args: Vec<_> = args
+                .iter()
+                .map(|arg| arg.as_deref().unwrap_or("?"))
+                .collect();
+            process.program = Some(args.join(" "));
+        }
+    }
+    /// Record a process exiting with the specified status
+    pub fn exit(&mut self, pid: Pid, status: ExitStatus) {
+        if let Some(process) = self.processes.get_mut(&pid) {
+            process.status = Some(status);
+            self.end(pid);
+        }
+    }
+    /// Record a process being gone, even if its exit status was never
+    /// seen
+    pub fn end(&mut self, pid: Pid) {
+        if let Some(process) = self.processes.get_mut(&pid) {
+            process.end.get_or_insert_with(Instant::now);
+        }
+    }
+    /// Print the tree, with children indented below their parents
+    pub fn print(&self, out: &mut dyn Write) -> Result<()> {
+        writeln!(out, "Process tree:")?;
+        for &root in &self.roots {
+            self.print_process(out, root, 0)?;
+        }
+        Ok(())
+    }
+    fn print_process(&self, out: &mut dyn Write, pid: Pid, depth: usize) -> Result<()> {
+        let process = match self.processes.get(&pid) {
+            Some(process) => process,
+            None => return Ok(()),
+        };
+        let runtime = process
+            .end
+            .unwrap_or_else(Instant::now)
+            .duration_since(process.start);
+        let state = match (process.status, process.end) {
+            (Some(status), _) => status.to_string(),
+            (None, Some(_)) => String::from("exited"),
+            (None, None) => String::from("running"),
+        };
+        writeln!(
+            out,
+            "{:indent$}{} {} ({}s, {})",
+            "",
+            pid,
+            process.program.as_deref().unwrap_or("?"),
+            timestamp::format_duration(runtime),
+            state,
+            indent = depth * 2
+        )?;
+        for &child in &process.children {
+            self.print_process(out, child, depth + 1)?;
+        }
+        Ok(())
+    }
+}
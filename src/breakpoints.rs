@@ -0,0 +1,144 @@
+//! Software breakpoints that stay in place as the process runs over
+//! them
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Result},
+};
+
+use crate::{
+    arch::{Arch, Native},
+    Event, EventData, Flags, Memory, Tracer,
+};
+
+/// Why the process stopped, as returned by `Breakpoints::next`
+#[derive(Debug)]
+pub enum Stop {
+    /// The process hit the breakpoint at this address. The
+    /// instruction pointer has been moved back to the address.
+    Breakpoint(usize),
+    /// Any other stop
+    Event(Event),
+}
+
+/// A set of software breakpoints in one process, keyed by address.
+///
+/// Each breakpoint replaces the first bytes of an instruction. When
+/// the process hits one, the original bytes have to be restored, the
+/// instruction single-stepped and the breakpoint written back before
+/// it can continue; `next` takes care of all of that.
+#[derive(Debug, Default)]
+pub struct Breakpoints {
+    /// The original bytes at each breakpoint
+    original: BTreeMap<usize, Vec<u8>>,
+    /// The breakpoint the process is stopped at, if any
+    stopped_at: Option<usize>,
+}
+impl Breakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns true if there is a breakpoint at the address
+    pub fn contains(&self, address: usize) -> bool {
+        self.original.contains_key(&address)
+    }
+    /// Returns the addresses of all breakpoints, in order
+    pub fn addresses(&self) -> impl Iterator<Item = usize> + '_ {
+        self.original.keys().copied()
+    }
+    /// Insert a breakpoint at the address. Returns false if there
+    /// already was one.
+    pub fn insert(&mut self, mem: &mut Memory, address: usize) -> Result<bool> {
+        if self.contains(address) {
+            return Ok(false);
+        }
+        let original = mem.set_breakpoint(address as *const u8)?;
+        self.original.insert(address, original);
+        Ok(true)
+    }
+    /// Remove the breakpoint at the address, restoring the original
+    /// instruction. Returns false if there was none.
+    pub fn remove(&mut self, mem: &mut Memory, address: usize) -> Result<bool> {
+        let original = match self.original.remove(&address) {
+            Some(original) => original,
+            None => return Ok(false),
+        };
+        mem.write(address as *const u8, &original)?;
+        if self.stopped_at == Some(address) {
+            self.stopped_at = None;
+        }
+        Ok(true)
+    }
+    /// Remove all breakpoints, restoring the original instructions
+    pub fn clear(&mut self, mem: &mut Memory) -> Result<()> {
+        let addresses: Vec<_> = self.addresses().collect();
+        for address in addresses {
+            self.remove(mem, address)?;
+        }
+        Ok(())
+    }
+    /// Continue the process until the next stop in `flags` or until it
+    /// hits one of the breakpoints. If the process is stopped at a
+    /// breakpoint, it's first stepped over with
+    /// `Flags::STOP_SINGLESTEP` so that the breakpoint stays in place.
+    ///
+    /// Events that don't stop the process, such as
+    /// `Flags::EVENT_CLONE`, are passed to `callback` as they arrive,
+    /// like with `EventHandler::from_callback`.
+    pub fn next<F, E>(
+        &mut self,
+        tracer: &mut Tracer,
+        flags: Flags,
+        mut callback: F,
+    ) -> std::result::Result<Stop, E>
+    where
+        F: FnMut(Event) -> std::result::Result<(), E>,
+        E: From<io::Error>,
+    {
+        if let Some(address) = self.stopped_at.take() {
+            self.step_over(tracer, address, &mut callback)?;
+        }
+
+        let event = tracer
+            .next_event(flags | Flags::STOP_BREAKPOINT)?
+            .from_callback(&mut callback)?;
+        if event.data != EventData::StopBreakpoint {
+            return Ok(Stop::Event(event));
+        }
+
+        let mut regs = tracer.regs.get_int()?;
+        let address = regs
+            .instruction_pointer()
+            .wrapping_sub(Native::BREAKPOINT_PC_ADJUSTMENT);
+        if !self.contains(address) {
+            // Not one of ours, such as a breakpoint compiled into the
+            // program
+            return Ok(Stop::Event(event));
+        }
+        regs.set_instruction_pointer(address);
+        tracer.regs.set_int(&regs)?;
+        self.stopped_at = Some(address);
+        Ok(Stop::Breakpoint(address))
+    }
+    /// Run the original instruction at a breakpoint the process is
+    /// stopped at, then put the breakpoint back
+    fn step_over<F, E>(
+        &mut self,
+        tracer: &mut Tracer,
+        address: usize,
+        callback: F,
+    ) -> std::result::Result<(), E>
+    where
+        F: FnMut(Event) -> std::result::Result<(), E>,
+        E: From<io::Error>,
+    {
+        let original = match self.original.get(&address) {
+            Some(original) => original,
+            None => return Ok(()),
+        };
+        tracer.mem.write(address as *const u8, original)?;
+        tracer.step(callback)?;
+        tracer.mem.set_breakpoint(address as *const u8)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+//! A minimal reader for the symbol tables of ELF64 little-endian
+//! executables, used to show addresses as `function+offset`
+
+use std::{
+    convert::TryInto,
+    fmt, fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNSYM: u32 = 11;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Little-endian reads from a byte slice, failing on out of bounds
+/// offsets rather than panicking
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.0.get(offset..end))
+            .ok_or_else(|| invalid("truncated ELF file"))
+    }
+    fn u8(&self, offset: usize) -> Result<u8> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+    fn u16(&self, offset: usize) -> Result<u16> {
+        Ok(u16::from_le_bytes(
+            self.bytes(offset, 2)?.try_into().unwrap(),
+        ))
+    }
+    fn u32(&self, offset: usize) -> Result<u32> {
+        Ok(u32::from_le_bytes(
+            self.bytes(offset, 4)?.try_into().unwrap(),
+        ))
+    }
+    fn u64(&self, offset: usize) -> Result<usize> {
+        Ok(u64::from_le_bytes(self.bytes(offset, 8)?.try_into().unwrap()) as usize)
+    }
+    /// A NUL terminated string, without the NUL
+    fn str(&self, offset: usize) -> Result<&'a [u8]> {
+        let rest = self
+            .0
+            .get(offset..)
+            .ok_or_else(|| invalid("bad string offset"))?;
+        let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        Ok(&rest[..len])
+    }
+}
+
+struct Section {
+    kind: u32,
+    offset: usize,
+    size: usize,
+    link: u32,
+    entsize: usize,
+}
+
+/// A function or data object defined by an executable
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub address: usize,
+    pub size: usize,
+}
+
+/// The symbols of one executable, sorted by address
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    symbols: Vec<Symbol>,
+}
+impl Symbols {
+    /// Read the symbols of the executable at the path
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::parse(&fs::read(path)?)
+    }
+    /// Read the symbols from the contents of an ELF64 little-endian
+    /// file. Both `.symtab` and `.dynsym` are used, if present.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let elf = Reader(data);
+        if elf.bytes(0, 4)? != b"\x7fELF" {
+            return Err(invalid("not an ELF file"));
+        }
+        if elf.u8(4)? != 2 || elf.u8(5)? != 1 {
+            return Err(invalid("only little-endian ELF64 is supported"));
+        }
+
+        let shoff = elf.u64(0x28)?;
+        let shentsize = elf.u16(0x3A)? as usize;
+        let shnum = elf.u16(0x3C)? as usize;
+
+        let mut sections = Vec::with_capacity(shnum);
+        for i in 0..shnum {
+            let offset = i
+                .checked_mul(shentsize)
+                .and_then(|offset| offset.checked_add(shoff))
+                .ok_or_else(|| invalid("truncated ELF file"))?;
+            let header = Reader(elf.bytes(offset, 64)?);
+            sections.push(Section {
+                kind: header.u32(4)?,
+                offset: header.u64(24)?,
+                size: header.u64(32)?,
+                link: header.u32(40)?,
+                entsize: header.u64(56)?,
+            });
+        }
+
+        let mut symbols = Vec::new();
+        for section in &sections {
+            if section.kind != SHT_SYMTAB && section.kind != SHT_DYNSYM {
+                continue;
+            }
+            if section.entsize < 24 {
+                return Err(invalid("bad symbol table entry size"));
+            }
+            let strtab = sections
+                .get(section.link as usize)
+                .ok_or_else(|| invalid("bad string table index"))?;
+            let strings = Reader(elf.bytes(strtab.offset, strtab.size)?);
+            let table = Reader(elf.bytes(section.offset, section.size)?);
+
+            for i in 0..section.size / section.entsize {
+                let entry = i * section.entsize;
+                let kind = table.u8(entry + 4)? & 0xF;
+                let address = table.u64(entry + 8)?;
+                if (kind != STT_FUNC && kind != STT_OBJECT) || address == 0 {
+                    continue;
+                }
+                let name = strings.str(table.u32(entry)? as usize)?;
+                if name.is_empty() {
+                    continue;
+                }
+                // Names are meant to be UTF-8, but nothing enforces it
+                symbols.push(Symbol {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    address,
+                    size: table.u64(entry + 16)?,
+                });
+            }
+        }
+
+        symbols.sort_by_key(|symbol| symbol.address);
+        symbols.dedup_by(|a, b| a.address == b.address && a.name == b.name);
+        Ok(Self { symbols })
+    }
+    /// Returns all symbols, sorted by address
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+    /// Find the symbol containing the address, along with the offset
+    /// of the address into it. Symbols with an unknown size of zero
+    /// are taken to extend up to the next symbol.
+    pub fn lookup(&self, address: usize) -> Option<(&Symbol, usize)> {
+        let i = match self
+            .symbols
+            .binary_search_by_key(&address, |symbol| symbol.address)
+        {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        let symbol = &self.symbols[i];
+        let offset = address - symbol.address;
+        if symbol.size != 0 && offset >= symbol.size {
+            return None;
+        }
+        Some((symbol, offset))
+    }
+    /// Returns a value which displays the address as `function+0x10`,
+    /// or in hex if it's not within any symbol
+    pub fn resolve(&self, address: usize) -> Resolved<'_> {
+        Resolved {
+            symbols: self,
+            address,
+        }
+    }
+}
+
+/// An address to display by symbol, see `Symbols::resolve`
+pub struct Resolved<'a> {
+    symbols: &'a Symbols,
+    address: usize,
+}
+impl<'a> fmt::Display for Resolved<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.symbols.lookup(self.address) {
+            Some((symbol, 0)) => write!(f, "{}", symbol.name),
+            Some((symbol, offset)) => write!(f, "{}+{:#x}", symbol.name, offset),
+            None => write!(f, "{:#X}", self.address),
+        }
+    }
+}
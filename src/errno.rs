@@ -0,0 +1,188 @@
+//! Names for the error numbers syscalls can fail with
+
+use std::fmt;
+
+use syscall::error::*;
+
+macro_rules! errnos {
+    ($($name:ident),* $(,)?) => {
+        /// Every errno known to `redox_syscall`, along with its name
+        pub static ERRNOS: &[(i32, &str)] = &[
+            $(($name, stringify!($name)),)*
+        ];
+    };
+}
+
+errnos! {
+    EPERM,
+    ENOENT,
+    ESRCH,
+    EINTR,
+    EIO,
+    ENXIO,
+    E2BIG,
+    ENOEXEC,
+    EBADF,
+    ECHILD,
+    EAGAIN,
+    ENOMEM,
+    EACCES,
+    EFAULT,
+    ENOTBLK,
+    EBUSY,
+    EEXIST,
+    EXDEV,
+    ENODEV,
+    ENOTDIR,
+    EISDIR,
+    EINVAL,
+    ENFILE,
+    EMFILE,
+    ENOTTY,
+    ETXTBSY,
+    EFBIG,
+    ENOSPC,
+    ESPIPE,
+    EROFS,
+    EMLINK,
+    EPIPE,
+    EDOM,
+    ERANGE,
+    EDEADLK,
+    ENAMETOOLONG,
+    ENOLCK,
+    ENOSYS,
+    ENOTEMPTY,
+    ELOOP,
+    EWOULDBLOCK,
+    ENOMSG,
+    EIDRM,
+    ECHRNG,
+    EL2NSYNC,
+    EL3HLT,
+    EL3RST,
+    ELNRNG,
+    EUNATCH,
+    ENOCSI,
+    EL2HLT,
+    EBADE,
+    EBADR,
+    EXFULL,
+    ENOANO,
+    EBADRQC,
+    EBADSLT,
+    EDEADLOCK,
+    EBFONT,
+    ENOSTR,
+    ENODATA,
+    ETIME,
+    ENOSR,
+    ENONET,
+    ENOPKG,
+    EREMOTE,
+    ENOLINK,
+    EADV,
+    ESRMNT,
+    ECOMM,
+    EPROTO,
+    EMULTIHOP,
+    EDOTDOT,
+    EBADMSG,
+    EOVERFLOW,
+    ENOTUNIQ,
+    EBADFD,
+    EREMCHG,
+    ELIBACC,
+    ELIBBAD,
+    ELIBSCN,
+    ELIBMAX,
+    ELIBEXEC,
+    EILSEQ,
+    ERESTART,
+    ESTRPIPE,
+    EUSERS,
+    ENOTSOCK,
+    EDESTADDRREQ,
+    EMSGSIZE,
+    EPROTOTYPE,
+    ENOPROTOOPT,
+    EPROTONOSUPPORT,
+    ESOCKTNOSUPPORT,
+    EOPNOTSUPP,
+    EPFNOSUPPORT,
+    EAFNOSUPPORT,
+    EADDRINUSE,
+    EADDRNOTAVAIL,
+    ENETDOWN,
+    ENETUNREACH,
+    ENETRESET,
+    ECONNABORTED,
+    ECONNRESET,
+    ENOBUFS,
+    EISCONN,
+    ENOTCONN,
+    ESHUTDOWN,
+    ETOOMANYREFS,
+    ETIMEDOUT,
+    ECONNREFUSED,
+    EHOSTDOWN,
+    EHOSTUNREACH,
+    EALREADY,
+    EINPROGRESS,
+    ESTALE,
+    EUCLEAN,
+    ENOTNAM,
+    ENAVAIL,
+    EISNAM,
+    EREMOTEIO,
+    EDQUOT,
+    ENOMEDIUM,
+    EMEDIUMTYPE,
+    ECANCELED,
+    ENOKEY,
+    EKEYEXPIRED,
+    EKEYREVOKED,
+    EKEYREJECTED,
+    EOWNERDEAD,
+    ENOTRECOVERABLE,
+}
+
+/// Look up the symbolic name of an errno, such as "ENOENT"
+pub fn name(errno: i32) -> Option<&'static str> {
+    ERRNOS
+        .iter()
+        .find(|&&(number, _)| number == errno)
+        .map(|&(_, name)| name)
+}
+
+/// Look up an errno by its symbolic name
+pub fn from_name(name: &str) -> Option<i32> {
+    ERRNOS
+        .iter()
+        .find(|&&(_, other)| other == name)
+        .map(|&(number, _)| number)
+}
+
+/// The outcome of a syscall, as returned by
+/// `IntRegisters::syscall_result`. Displays like strace does, such
+/// as `3 (0x3)` or `-1 ENOENT (No such file or directory)`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SyscallResult(pub syscall::Result<usize>);
+
+impl SyscallResult {
+    /// Interpret a raw return value
+    pub fn from_raw(ret: usize) -> Self {
+        SyscallResult(Error::demux(ret))
+    }
+}
+impl fmt::Display for SyscallResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Ok(value) => write!(f, "{} ({:#X})", value, value),
+            Err(ref err) => match name(err.errno) {
+                Some(name) => write!(f, "-1 {} ({})", name, err),
+                None => write!(f, "-1 {} ({})", err.errno, err),
+            },
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Keeping track of what the file descriptors of a traced process
+//! refer to
+
+use std::collections::HashMap;
+
+use crate::syscalls::{Arg, DecodedSyscall};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    path: String,
+    cloexec: bool,
+}
+
+/// The paths of the open file descriptors of one process, as far as
+/// they can be known from the syscalls it was seen making.
+/// Descriptors opened before tracing began are unknown.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FdTable {
+    fds: HashMap<usize, Entry>,
+}
+impl FdTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the path a file descriptor was opened with, if known
+    pub fn get(&self, fd: usize) -> Option<&str> {
+        self.fds.get(&fd).map(|entry| &*entry.path)
+    }
+    /// Returns true if `update` needs the arguments of this syscall
+    /// decoded with memory available, both at entry and at exit, or
+    /// if `exec` needs to be called at its entry
+    pub fn tracks(number: usize) -> bool {
+        matches!(
+            number,
+            syscall::SYS_OPEN
+                | syscall::SYS_CLOSE
+                | syscall::SYS_DUP
+                | syscall::SYS_DUP2
+                | syscall::SYS_FCNTL
+                | syscall::SYS_PIPE2
+                | syscall::SYS_FEXEC
+        )
+    }
+    /// Update the table after the syscall returned `ret`. Failed
+    /// syscalls are ignored.
+    pub fn update(&mut self, syscall: &DecodedSyscall, ret: usize) {
+        let ret = match syscall::Error::demux(ret) {
+            Ok(ret) => ret,
+            Err(_) => return,
+        };
+        match (syscall.number, &*syscall.args) {
+            (syscall::SYS_OPEN, [Arg::Path(path), Arg::OpenFlags(flags)]) => {
+                if let Some(ref path) = path.data {
+                    self.insert(ret, String::from_utf8_lossy(path).into_owned(), *flags);
+                }
+            },
+            (syscall::SYS_CLOSE, [Arg::Fd(fd)]) => {
+                self.fds.remove(fd);
+            },
+            (syscall::SYS_DUP, [Arg::Fd(fd), Arg::Bytes(buf)])
+            | (syscall::SYS_DUP2, [Arg::Fd(fd), _, Arg::Bytes(buf)]) => {
+                if let Some(path) = self.get(*fd) {
+                    let path = match buf.data {
+                        Some(ref buf) if !buf.is_empty() => {
+                            format!("{}/{}", path, String::from_utf8_lossy(buf))
+                        },
+                        _ => path.to_string(),
+                    };
+                    self.insert(ret, path, 0);
+                } else {
+                    self.fds.remove(&ret);
+                }
+            },
+            (syscall::SYS_FCNTL, [Arg::Fd(fd), Arg::FcntlCmd(cmd), Arg::Hex(arg)]) => match *cmd {
+                syscall::F_DUPFD => match self.get(*fd) {
+                    Some(path) => {
+                        let path = path.to_string();
+                        self.insert(ret, path, 0);
+                    },
+                    None => {
+                        self.fds.remove(&ret);
+                    },
+                },
+                syscall::F_SETFD => {
+                    if let Some(entry) = self.fds.get_mut(fd) {
+                        entry.cloexec = arg & syscall::O_CLOEXEC == syscall::O_CLOEXEC;
+                    }
+                },
+                _ => (),
+            },
+            (syscall::SYS_PIPE2, [Arg::FdPair(fds), Arg::OpenFlags(flags)]) => {
+                if let Some([read, write]) = fds.value {
+                    self.insert(read, String::from("pipe:"), *flags);
+                    self.insert(write, String::from("pipe:"), *flags);
+                }
+            },
+            _ => (),
+        }
+    }
+    /// Forget the descriptors that are closed on exec. Call this at
+    /// the entry of `fexec`, as it never returns if it succeeds.
+    pub fn exec(&mut self) {
+        self.fds.retain(|_, entry| !entry.cloexec);
+    }
+    fn insert(&mut self, fd: usize, path: String, flags: usize) {
+        let cloexec = flags & syscall::O_CLOEXEC == syscall::O_CLOEXEC;
+        self.fds.insert(fd, Entry { path, cloexec });
+    }
+}
@@ -13,8 +13,24 @@ use std::{
     ptr, slice,
 };
 
-mod arch;
+pub mod arch;
+pub mod backtrace;
+pub mod breakpoints;
+pub mod elf;
+pub mod errno;
+pub mod fds;
 mod kernel;
+pub mod plain;
+pub mod session;
+pub mod signal;
+pub mod syscalls;
+
+use arch::{Arch, Native};
+use errno::SyscallResult;
+use plain::PlainData;
+use signal::SignalNumber;
+use syscall::flag::CloneFlags;
+use syscalls::DecodedSyscall;
 
 macro_rules! trace {
     ($($inner:expr),*) => {{
@@ -53,22 +69,72 @@ bitflags! {
 
 pub type Pid = usize;
 
+/// The integer registers of the native architecture, see
+/// `arch::Native`
 #[derive(Clone, Copy, Debug)]
-pub struct IntRegisters(pub syscall::IntRegisters);
+pub struct IntRegisters(pub <Native as Arch>::Registers);
 
 impl IntRegisters {
+    /// Decode the syscall these registers describe. Pointer arguments
+    /// are only followed if `mem` is available, and no more than
+    /// `limit` bytes of a byte buffer are read.
+    pub fn decode_syscall(&self, mem: Option<&mut Memory>, limit: usize) -> DecodedSyscall {
+        Native::decode_syscall(mem, self, limit)
+    }
     pub fn format_syscall_bare(&self) -> String {
-        arch::format_syscall(None, &self)
+        self.decode_syscall(None, 0).to_string()
     }
     pub fn format_syscall_full(&self, mem: &mut Memory) -> String {
-        arch::format_syscall(Some(mem), &self)
+        self.decode_syscall(Some(mem), syscalls::DEFAULT_STRING_LIMIT).to_string()
+    }
+    /// Returns the number of the syscall being made, at a pre-syscall
+    /// stop
+    pub fn syscall_number(&self) -> usize {
+        Native::syscall_number(self)
+    }
+    /// Returns the registers holding the syscall arguments, in order
+    pub fn args(&self) -> [usize; 6] {
+        Native::syscall_args(self)
+    }
+    /// Replace a syscall argument before the kernel sees it. Panics if
+    /// `index` is 6 or more.
+    pub fn set_arg(&mut self, index: usize, value: usize) {
+        Native::set_syscall_arg(self, index, value)
     }
     pub fn return_value(&self) -> usize {
-        arch::return_value(&self)
+        Native::return_value(self)
+    }
+    /// Returns the address of the next instruction to execute
+    pub fn instruction_pointer(&self) -> usize {
+        Native::instruction_pointer(self)
+    }
+    /// Make the process continue at another address
+    pub fn set_instruction_pointer(&mut self, address: usize) {
+        Native::set_instruction_pointer(self, address)
+    }
+    /// Returns the address of the top of the stack
+    pub fn stack_pointer(&self) -> usize {
+        Native::stack_pointer(self)
+    }
+    /// Returns the address of the current stack frame, if the code
+    /// running keeps one
+    pub fn frame_pointer(&self) -> usize {
+        Native::frame_pointer(self)
+    }
+    /// Set the value the syscall returns. Together with
+    /// `Flags::FLAG_IGNORE`, this lets a tracer skip a syscall and
+    /// return a result of its own choosing.
+    pub fn set_return_value(&mut self, value: usize) {
+        Native::set_return_value(self, value)
+    }
+    /// Interpret the return value of the syscall that just finished as
+    /// either a value or an error
+    pub fn syscall_result(&self) -> SyscallResult {
+        SyscallResult::from_raw(self.return_value())
     }
 }
 impl Deref for IntRegisters {
-    type Target = syscall::IntRegisters;
+    type Target = <Native as Arch>::Registers;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -80,9 +146,13 @@ impl DerefMut for IntRegisters {
     }
 }
 
+/// The floating point registers, which `syscall` only defines for
+/// x86_64
+#[cfg(target_arch = "x86_64")]
 #[derive(Clone, Copy, Debug)]
 pub struct FloatRegisters(pub syscall::FloatRegisters);
 
+#[cfg(target_arch = "x86_64")]
 impl Deref for FloatRegisters {
     type Target = syscall::FloatRegisters;
 
@@ -90,17 +160,64 @@ impl Deref for FloatRegisters {
         &self.0
     }
 }
+#[cfg(target_arch = "x86_64")]
 impl DerefMut for FloatRegisters {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
 }
 
+/// The status a process exited with, as reported by
+/// `EventData::StopExit`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ExitStatus(pub usize);
+impl ExitStatus {
+    /// Returns the exit code, if the process exited by itself
+    pub fn code(self) -> Option<usize> {
+        if syscall::wifexited(self.0) {
+            Some(syscall::wexitstatus(self.0))
+        } else {
+            None
+        }
+    }
+    /// Returns the signal that killed the process, if any
+    pub fn signal(self) -> Option<usize> {
+        if syscall::wifsignaled(self.0) {
+            Some(syscall::wtermsig(self.0))
+        } else {
+            None
+        }
+    }
+}
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.code(), self.signal()) {
+            (_, Some(signal)) => write!(f, "killed by {}", SignalNumber(signal)),
+            (Some(code), _) => write!(f, "exit status {}", code),
+            (None, None) => write!(f, "status {:#X}", self.0),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum EventData {
-    EventClone(usize),
+    /// A new process was cloned off. The flags are empty if the kernel
+    /// doesn't report them.
+    EventClone {
+        pid: Pid,
+        flags: CloneFlags,
+    },
+    /// The process is about to make a syscall
+    StopPreSyscall,
+    /// A syscall has finished
+    StopPostSyscall,
+    /// One instruction has been executed
+    StopSinglestep,
+    /// The process hit a breakpoint instruction
+    StopBreakpoint,
+    /// The signal number and the handler that's about to run
     StopSignal(usize, usize),
-    StopExit(usize),
+    StopExit(ExitStatus),
     Unknown(usize, usize, usize, usize, usize, usize),
 }
 
@@ -114,9 +231,16 @@ impl Event {
         Self {
             cause: Flags::from_bits_truncate(inner.cause.bits()),
             data: match inner.cause {
-                syscall::PTRACE_EVENT_CLONE => EventData::EventClone(inner.a),
+                syscall::PTRACE_EVENT_CLONE => EventData::EventClone {
+                    pid: inner.a,
+                    flags: CloneFlags::from_bits_truncate(inner.b),
+                },
+                syscall::PTRACE_STOP_PRE_SYSCALL => EventData::StopPreSyscall,
+                syscall::PTRACE_STOP_POST_SYSCALL => EventData::StopPostSyscall,
+                syscall::PTRACE_STOP_SINGLESTEP => EventData::StopSinglestep,
+                syscall::PTRACE_STOP_BREAKPOINT => EventData::StopBreakpoint,
                 syscall::PTRACE_STOP_SIGNAL => EventData::StopSignal(inner.a, inner.b),
-                syscall::PTRACE_STOP_EXIT => EventData::StopExit(inner.a),
+                syscall::PTRACE_STOP_EXIT => EventData::StopExit(ExitStatus(inner.a)),
                 _ => EventData::Unknown(inner.a, inner.b, inner.c, inner.d, inner.e, inner.f),
             },
         }
@@ -134,22 +258,25 @@ impl Registers {
             int: File::open(format!("proc:{}/regs/int", pid))?,
         })
     }
+    #[cfg(target_arch = "x86_64")]
     pub fn get_float(&mut self) -> Result<FloatRegisters> {
         let mut regs = syscall::FloatRegisters::default();
         trace!(self.float.read(&mut regs)?, &regs);
         Ok(FloatRegisters(regs))
     }
+    #[cfg(target_arch = "x86_64")]
     pub fn set_float(&mut self, regs: &FloatRegisters) -> Result<()> {
         trace!(self.float.write(&regs)?, &regs);
         Ok(())
     }
     pub fn get_int(&mut self) -> Result<IntRegisters> {
-        let mut regs = syscall::IntRegisters::default();
-        trace!(self.int.read(&mut regs)?, &regs);
+        let int = &mut self.int;
+        let regs = plain::from_bytes(|bytes| int.read(bytes).map(|_| ()))?;
+        trace!(&regs);
         Ok(IntRegisters(regs))
     }
     pub fn set_int(&mut self, regs: &IntRegisters) -> Result<()> {
-        trace!(self.int.write(&regs)?, &regs);
+        trace!(self.int.write(&plain::to_bytes(&regs.0))?, &regs);
         Ok(())
     }
 }
@@ -163,6 +290,10 @@ pub struct Memory {
     pub file: File,
 }
 impl Memory {
+    /// The most bytes `read_vec` reads at once. Lengths usually come
+    /// from the tracee, and can't be trusted not to be garbage.
+    pub const MAX_READ: usize = 16 * 1024 * 1024;
+
     pub fn attach(pid: Pid) -> Result<Self> {
         Ok(Self {
             file: File::open(format!("proc:{}/mem", pid))?,
@@ -180,15 +311,75 @@ impl Memory {
         trace!(memory);
         Ok(())
     }
+    /// Reads one value of a plain data type from the specified memory
+    /// address
+    pub fn read_value<T: PlainData>(&mut self, address: *const T) -> Result<T> {
+        plain::from_bytes(|bytes| self.read(address as *const u8, bytes))
+    }
+    /// Reads `len` consecutive values of a plain data type, starting
+    /// at the specified memory address. Fails with `EINVAL` if that's
+    /// more than `MAX_READ` bytes.
+    pub fn read_vec<T: PlainData>(&mut self, address: *const T, len: usize) -> Result<Vec<T>> {
+        let size = len
+            .checked_mul(mem::size_of::<T>())
+            .filter(|&size| size <= Self::MAX_READ)
+            .ok_or_else(|| io::Error::from_raw_os_error(syscall::EINVAL))?;
+        let mut vec = Vec::<T>::with_capacity(len);
+        unsafe {
+            ptr::write_bytes(vec.as_mut_ptr(), 0, len);
+            let bytes = slice::from_raw_parts_mut(vec.as_mut_ptr() as *mut u8, size);
+            self.read(address as *const u8, bytes)?;
+            vec.set_len(len);
+        }
+        Ok(vec)
+    }
+    /// Reads a NUL terminated string from the specified memory
+    /// address, without the NUL. Stops early after `max` bytes or
+    /// where the readable memory ends.
+    pub fn read_cstring(&mut self, address: *const u8, max: usize) -> Result<Vec<u8>> {
+        let mut string = Vec::new();
+        let mut chunk = [0; 64];
+        self.file.seek(SeekFrom::Start(address as u64))?;
+        while string.len() < max {
+            let len = chunk.len().min(max - string.len());
+            let len = self.file.read(&mut chunk[..len])?;
+            let chunk = &chunk[..len];
+            if let Some(end) = chunk.iter().position(|&byte| byte == 0) {
+                string.extend_from_slice(&chunk[..end]);
+                break;
+            }
+            if chunk.is_empty() {
+                break;
+            }
+            string.extend_from_slice(chunk);
+        }
+        trace!(&string);
+        Ok(string)
+    }
+    /// Writes one value of a plain data type to the specified memory
+    /// address. Any padding bytes are written as zeroes.
+    pub fn write_value<T: PlainData>(&mut self, address: *const T, value: &T) -> Result<()> {
+        self.write(address as *const u8, &plain::to_bytes(value))
+    }
+    /// Reads the raw bytes of the instruction at the specified memory
+    /// address. Instructions aren't decoded, so this returns as many
+    /// bytes as the longest possible instruction, or fewer where the
+    /// readable memory ends.
+    pub fn read_instruction(&mut self, address: *const u8) -> Result<Vec<u8>> {
+        let mut memory = vec![0; Native::MAX_INSTRUCTION_LEN];
+        self.file.seek(SeekFrom::Start(address as u64))?;
+        let len = self.file.read(&mut memory)?;
+        memory.truncate(len);
+        trace!(&memory);
+        Ok(memory)
+    }
     /// Writes a software breakpoint to the specified memory address, and
-    /// returns the previous instruction.
-    pub fn set_breakpoint(&mut self, address: *const u8) -> Result<u8> {
-        let mut previous = [0];
+    /// returns the bytes it replaced.
+    pub fn set_breakpoint(&mut self, address: *const u8) -> Result<Vec<u8>> {
+        let mut previous = vec![0; Native::BREAKPOINT.len()];
         self.read(address, &mut previous)?;
-
-        arch::set_breakpoint(self, address)?;
-
-        Ok(previous[0])
+        self.write(address, Native::BREAKPOINT)?;
+        Ok(previous)
     }
     pub fn cursor(&mut self) -> Result<u64> {
         self.file.seek(SeekFrom::Current(0))
@@ -204,6 +395,9 @@ pub struct Tracer {
     pub file: File,
     pub regs: Registers,
     pub mem: Memory,
+    /// Set by `detach`, so that dropping the tracer doesn't detach
+    /// again
+    detached: bool,
 }
 impl Tracer {
     /// Attach to a tracer with the specified PID. This will stop it.
@@ -216,6 +410,7 @@ impl Tracer {
                 .open(format!("proc:{}/trace", pid))?,
             regs: Registers::attach(pid)?,
             mem: Memory::attach(pid)?,
+            detached: false,
         })
     }
     /// Set a breakpoint on the next specified stop, and wait for the
@@ -233,6 +428,18 @@ impl Tracer {
             )
         })
     }
+    /// Execute a single instruction, and return the registers
+    /// afterwards. Events that don't stop the process, such as a
+    /// `Flags::EVENT_CLONE` from stepping over a syscall, are passed
+    /// to `callback` like with `EventHandler::from_callback`.
+    pub fn step<F, E>(&mut self, callback: F) -> std::result::Result<IntRegisters, E>
+    where
+        F: FnMut(Event) -> std::result::Result<(), E>,
+        E: From<io::Error>,
+    {
+        self.next_event(Flags::STOP_SINGLESTEP)?.from_callback(callback)?;
+        Ok(self.regs.get_int()?)
+    }
     /// Similarly to `next`, but instead of conveniently returning a
     /// breakpoint event, it returns an event handler that lets you
     /// handle events yourself.
@@ -240,6 +447,49 @@ impl Tracer {
         trace!(flags, self.file.write(&flags.bits().to_ne_bytes())?);
         Ok(EventHandler { inner: self })
     }
+    /// Emulate the syscall the process is stopped at, instead of
+    /// letting the kernel run it: Write each `(address, data)` pair of
+    /// `output` to the process' memory, set the syscall's return value
+    /// to `result`, and continue like `next_event(flags)` with the
+    /// syscall skipped.
+    ///
+    /// This must only be called at a `Flags::STOP_PRE_SYSCALL` stop.
+    /// At any other stop there is no syscall to skip, and the process
+    /// would just have its return register overwritten. If `flags`
+    /// contains `Flags::STOP_POST_SYSCALL`, the post-syscall stop is
+    /// still reported and shows the emulated result.
+    pub fn emulate_syscall(
+        &mut self,
+        result: syscall::Result<usize>,
+        output: &[(*const u8, &[u8])],
+        flags: Flags,
+    ) -> Result<EventHandler> {
+        self.prepare_emulation(result, output)?;
+        self.next_event(flags | Flags::FLAG_IGNORE)
+    }
+    fn prepare_emulation(
+        &mut self,
+        result: syscall::Result<usize>,
+        output: &[(*const u8, &[u8])],
+    ) -> Result<()> {
+        for &(address, data) in output {
+            self.mem.write(address, data)?;
+        }
+        let mut regs = self.regs.get_int()?;
+        regs.set_return_value(syscall::Error::mux(result));
+        self.regs.set_int(&regs)
+    }
+    /// Stop tracing the process. Any breakpoint set with `next` is
+    /// cleared, so the process keeps running as if it was never
+    /// traced. Dropping the tracer does the same, but ignores errors.
+    pub fn detach(mut self) -> Result<()> {
+        self.detached = true;
+        self.clear_breakpoint()
+    }
+    fn clear_breakpoint(&mut self) -> Result<()> {
+        trace!(self.file.write(&Flags::empty().bits().to_ne_bytes())?);
+        Ok(())
+    }
     /// Convert this tracer to be nonblocking. Setting breakpoints
     /// will no longer wait by default, but you will gain access to a
     /// `wait` function which will do the same as in blocking
@@ -297,6 +547,15 @@ impl Tracer {
         }))
     }
 }
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        // The process may already be gone, in which case there's
+        // nothing to clean up
+        if !self.detached {
+            let _ = self.clear_breakpoint();
+        }
+    }
+}
 impl fmt::Debug for Tracer {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Tracer(...)")
@@ -366,6 +625,7 @@ impl NonblockTracer {
                     .open(format!("proc:{}/trace", pid))?,
                 regs: Registers::attach(pid)?,
                 mem: Memory::attach(pid)?,
+                detached: false,
             },
         })
     }
@@ -377,6 +637,17 @@ impl NonblockTracer {
         trace!(flags, self.file.write(&flags.bits().to_ne_bytes())?);
         Ok(())
     }
+    /// Same as `Tracer::emulate_syscall`, but like `next`, only sets
+    /// the breakpoint without waiting for it.
+    pub fn emulate_syscall(
+        &mut self,
+        result: syscall::Result<usize>,
+        output: &[(*const u8, &[u8])],
+        flags: Flags,
+    ) -> Result<()> {
+        self.inner.prepare_emulation(result, output)?;
+        self.next(flags | Flags::FLAG_IGNORE)
+    }
     /// Stub that prevents you from accidentally calling `next_event`
     /// on the tracer, do not use.
     #[deprecated(
@@ -387,6 +658,11 @@ impl NonblockTracer {
         panic!("Tried to use next_event on a nonblocking tracer")
     }
 
+    /// Same as `Tracer::detach`. Any yet unread events are ignored.
+    pub fn detach(self) -> Result<()> {
+        self.inner.detach()
+    }
+
     /// Convert this tracer back to a blocking version. Any yet unread
     /// events are ignored.
     pub fn blocking(self) -> Result<Tracer> {
@@ -24,6 +24,13 @@ pub const TRACE_FLAGS: Flags = Flags::from_bits_truncate(
 fn main() -> Result<()> {
     let opt = mode::parse_args();
 
+    #[cfg(feature = "advanced")]
+    {
+        if !opt.pids.is_empty() {
+            return mode::attach_main(opt);
+        }
+    }
+
     let mut file = None;
     for mut path in env::split_paths(&env::var_os("PATH").unwrap_or(OsString::new())) {
         path.push(&opt.cmd[0]);
@@ -92,9 +99,16 @@ fn parent(path: PathBuf, pid: Pid, opt: mode::Opt) -> Result<()> {
     // There will first be a post-syscall for `kill`.
     tracer.next(Flags::STOP_POST_SYSCALL)?;
 
-    match mode::inner_main(pid, tracer, opt) {
-        Err(ref err) if err.raw_os_error() == Some(syscall::ESRCH) => {
-            e(syscall::waitpid(pid, &mut status, syscall::WNOHANG))?;
+    // Tracing ends either once every traced process is gone, or with
+    // ESRCH as soon as the process exits
+    let result = mode::inner_main(pid, &path, tracer, opt);
+    match result {
+        Err(ref err) if err.raw_os_error() != Some(syscall::ESRCH) => result,
+        _ => {
+            // The process keeps running if we detached from it
+            if e(syscall::waitpid(pid, &mut status, syscall::WNOHANG))? != pid {
+                return Ok(());
+            }
             if syscall::wifexited(status) {
                 println!(
                     "Process exited with status {}",
@@ -106,6 +120,5 @@ fn parent(path: PathBuf, pid: Pid, opt: mode::Opt) -> Result<()> {
             }
             Ok(())
         },
-        other => other,
     }
 }
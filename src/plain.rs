@@ -0,0 +1,104 @@
+//! Types that can be safely copied in and out of tracee memory as
+//! raw bytes
+
+use std::{
+    io::Result,
+    mem::{self, MaybeUninit},
+    slice,
+};
+use syscall::data::{Event, Map, PtraceEvent, SigAction, Stat, TimeSpec};
+
+/// A marker for types where every bit pattern of the right size is a
+/// valid value, such as integers and the `repr(C)` structs the kernel
+/// fills in. Only these can be read from tracee memory, as nothing
+/// stops the tracee from putting garbage there.
+///
+/// # Safety
+///
+/// The type must not contain references, `bool`s, `char`s, enums or
+/// anything else with invalid bit patterns. Function pointers may
+/// only appear wrapped in an `Option`, so that null is `None`.
+///
+/// If the type has padding bytes, it must override `copy_to_bytes`,
+/// as the default implementation reads every byte of the value and
+/// padding is uninitialized.
+pub unsafe trait PlainData: Copy + 'static {
+    /// Copy the bytes of this value into `bytes`, which is exactly as
+    /// large as the value. Padding bytes are left as they are.
+    fn copy_to_bytes(&self, bytes: &mut [u8]) {
+        // Without padding, every byte of the value is initialized
+        let value = unsafe {
+            slice::from_raw_parts(self as *const Self as *const u8, mem::size_of::<Self>())
+        };
+        bytes.copy_from_slice(value);
+    }
+}
+
+/// Create a value from the bytes `fill` puts in place, which start
+/// out zeroed
+pub(crate) fn from_bytes<T, F>(fill: F) -> Result<T>
+where
+    T: PlainData,
+    F: FnOnce(&mut [u8]) -> Result<()>,
+{
+    let mut value = MaybeUninit::<T>::zeroed();
+    // The zeroed value is fully initialized as bytes, and once
+    // overwritten any bytes are a valid T
+    let bytes =
+        unsafe { slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, mem::size_of::<T>()) };
+    fill(bytes)?;
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Returns the bytes of a value, with any padding zeroed
+pub(crate) fn to_bytes<T: PlainData>(value: &T) -> Vec<u8> {
+    let mut bytes = vec![0; mem::size_of::<T>()];
+    value.copy_to_bytes(&mut bytes);
+    bytes
+}
+
+macro_rules! plain_data {
+    ($($ty:ty),*) => {
+        $(unsafe impl PlainData for $ty {})*
+    };
+}
+
+/// Implement `PlainData` for a struct with padding, by copying it
+/// one field at a time. The fields must be plain data themselves.
+macro_rules! padded_plain_data {
+    ($($ty:ty { $($field:ident),* })*) => {
+        $(unsafe impl PlainData for $ty {
+            fn copy_to_bytes(&self, bytes: &mut [u8]) {
+                let base = self as *const Self as usize;
+                $(
+                    let start = &self.$field as *const _ as usize - base;
+                    let end = start + mem::size_of_val(&self.$field);
+                    self.$field.copy_to_bytes(&mut bytes[start..end]);
+                )*
+            }
+        })*
+    };
+}
+
+plain_data!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+plain_data!(Event, Map, PtraceEvent, SigAction);
+padded_plain_data! {
+    Stat {
+        st_dev, st_ino, st_mode, st_nlink, st_uid, st_gid, st_size, st_blksize, st_blocks,
+        st_mtime, st_mtime_nsec, st_atime, st_atime_nsec, st_ctime, st_ctime_nsec
+    }
+    TimeSpec { tv_sec, tv_nsec }
+}
+
+unsafe impl<T: PlainData, const N: usize> PlainData for [T; N] {
+    fn copy_to_bytes(&self, bytes: &mut [u8]) {
+        // Arrays have no padding between elements, but the elements
+        // may have some of their own
+        for (item, bytes) in self
+            .iter()
+            .zip(bytes.chunks_mut(mem::size_of::<T>().max(1)))
+        {
+            item.copy_to_bytes(bytes);
+        }
+    }
+}
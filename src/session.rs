@@ -0,0 +1,230 @@
+//! Tracing many processes at once from a single thread, by waiting
+//! for whichever of them stops first through the `event:` scheme
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{self, prelude::*, ErrorKind, Result},
+    mem,
+    os::unix::io::AsRawFd,
+};
+
+use crate::{Event, EventData, Flags, NonblockTracer, Pid};
+
+struct Traced {
+    tracer: NonblockTracer,
+    /// Extra flags to continue the process with, see
+    /// `TraceSession::continue_with`
+    flags: Flags,
+    /// Set once the process reported `Flags::STOP_EXIT`, after which
+    /// it's only continued until it's gone
+    exited: bool,
+}
+
+/// A set of traced processes, keyed by pid, and the events they
+/// report.
+///
+/// Each call to `next_event` returns one event. A process stays
+/// stopped while its events are being handled, so its registers and
+/// memory can be inspected and changed, and is continued the next
+/// time `next_event` needs to wait for more events.
+pub struct TraceSession {
+    events: File,
+    processes: HashMap<Pid, Traced>,
+    /// The stops to ask for each time a process is continued
+    flags: Flags,
+    follow_clones: bool,
+    /// The process whose events are being handled
+    current: Option<Pid>,
+    /// Events of `current` that have been read but not returned yet
+    queue: VecDeque<Event>,
+    /// Processes that disappeared without a `Flags::STOP_EXIT`
+    lost: Vec<Pid>,
+}
+impl TraceSession {
+    /// Create a session that continues each process until the next
+    /// stop in `flags`
+    pub fn new(flags: Flags) -> Result<Self> {
+        Ok(Self {
+            events: File::open("event:")?,
+            processes: HashMap::new(),
+            flags,
+            follow_clones: false,
+            current: None,
+            queue: VecDeque::new(),
+            lost: Vec::new(),
+        })
+    }
+    /// Set whether children reported by `EventData::EventClone` are
+    /// traced as well. They're attached before the clone event is
+    /// returned from `next_event`.
+    pub fn set_follow_clones(&mut self, follow: bool) {
+        self.follow_clones = follow;
+    }
+    /// Start tracing a process, continuing it until its next stop
+    pub fn add(&mut self, pid: Pid, mut tracer: NonblockTracer) -> Result<()> {
+        tracer.next(self.flags)?;
+
+        self.events.write_all(&syscall::Event {
+            id: tracer.file.as_raw_fd() as usize,
+            flags: syscall::EVENT_READ,
+            data: pid,
+        })?;
+
+        self.processes.insert(
+            pid,
+            Traced {
+                tracer,
+                flags: Flags::empty(),
+                exited: false,
+            },
+        );
+        Ok(())
+    }
+    /// Attach to a process and start tracing it, see `add`
+    pub fn attach(&mut self, pid: Pid) -> Result<()> {
+        self.add(pid, NonblockTracer::attach(pid)?)
+    }
+    /// Stop tracing a process without detaching from it, and return
+    /// its tracer
+    pub fn remove(&mut self, pid: Pid) -> Option<NonblockTracer> {
+        if self.current == Some(pid) {
+            self.current = None;
+            self.queue.clear();
+        }
+        self.processes.remove(&pid).map(|traced| traced.tracer)
+    }
+    /// Stop tracing a process and let it run freely
+    pub fn detach(&mut self, pid: Pid) -> Result<()> {
+        match self.remove(pid) {
+            Some(tracer) => tracer.detach(),
+            None => Ok(()),
+        }
+    }
+    /// Returns the tracer of a process, to read or change its
+    /// registers and memory while it's stopped
+    pub fn tracer(&mut self, pid: Pid) -> Option<&mut NonblockTracer> {
+        self.processes
+            .get_mut(&pid)
+            .map(|traced| &mut traced.tracer)
+    }
+    /// Returns the pids of all traced processes
+    pub fn pids(&self) -> impl Iterator<Item = Pid> + '_ {
+        self.processes.keys().copied()
+    }
+    /// Returns true if no processes are left
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty()
+    }
+    /// Add to the flags a process is continued with once its current
+    /// events are handled, such as `Flags::FLAG_IGNORE` to skip the
+    /// syscall it's stopped at
+    pub fn continue_with(&mut self, pid: Pid, flags: Flags) {
+        if let Some(traced) = self.processes.get_mut(&pid) {
+            traced.flags |= flags;
+        }
+    }
+    /// Returns the processes that disappeared since the last call
+    /// without reporting `Flags::STOP_EXIT`, such as when killed.
+    /// Processes that do report it are removed as soon as they're
+    /// gone, but not listed here.
+    pub fn take_lost(&mut self) -> Vec<Pid> {
+        mem::take(&mut self.lost)
+    }
+
+    /// Wait for the next event of any process. Returns `None` once no
+    /// processes are left.
+    ///
+    /// Interrupted reads are returned as errors of the kind
+    /// `ErrorKind::Interrupted`, after which it's fine to call this
+    /// again.
+    pub fn next_event(&mut self) -> Result<Option<(Pid, Event)>> {
+        loop {
+            if let Some(pid) = self.current {
+                if let Some(event) = self.queue.pop_front() {
+                    self.handle(pid, &event)?;
+                    return Ok(Some((pid, event)));
+                }
+                self.current = None;
+                self.resume(pid)?;
+            }
+            if self.processes.is_empty() {
+                return Ok(None);
+            }
+
+            let mut event = syscall::Event::default();
+            if self.events.read(&mut event)? < mem::size_of::<syscall::Event>() {
+                return Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "short read from event:",
+                ));
+            }
+            let pid = event.data;
+            let traced = match self.processes.get(&pid) {
+                Some(traced) => traced,
+                None => continue,
+            };
+
+            for event in traced.tracer.events()? {
+                match event {
+                    Ok(event) => self.queue.push_back(event),
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(ref err) if err.raw_os_error() == Some(syscall::ESRCH) => {
+                        self.lose(pid);
+                        break;
+                    },
+                    Err(err) => return Err(err),
+                }
+            }
+            if self.processes.contains_key(&pid) {
+                self.current = Some(pid);
+            } else {
+                self.queue.clear();
+            }
+        }
+    }
+    /// Keep track of an event as it's returned
+    fn handle(&mut self, pid: Pid, event: &Event) -> Result<()> {
+        match event.data {
+            EventData::EventClone { pid: child, .. } if self.follow_clones => {
+                match NonblockTracer::attach(child) {
+                    Ok(tracer) => self.add(child, tracer),
+                    // The child is already gone
+                    Err(ref err) if err.raw_os_error() == Some(syscall::ESRCH) => Ok(()),
+                    Err(err) => Err(err),
+                }
+            },
+            EventData::StopExit(_) => {
+                if let Some(traced) = self.processes.get_mut(&pid) {
+                    traced.exited = true;
+                }
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+    /// Continue a process once all its events are handled
+    fn resume(&mut self, pid: Pid) -> Result<()> {
+        let traced = match self.processes.get_mut(&pid) {
+            Some(traced) => traced,
+            None => return Ok(()),
+        };
+        let flags = self.flags | mem::replace(&mut traced.flags, Flags::empty());
+        match traced.tracer.next(flags) {
+            Err(ref err) if err.raw_os_error() == Some(syscall::ESRCH) => {
+                self.lose(pid);
+                Ok(())
+            },
+            result => result,
+        }
+    }
+    /// Forget about a process that is gone
+    fn lose(&mut self, pid: Pid) {
+        if let Some(traced) = self.processes.get(&pid) {
+            if !traced.exited {
+                self.lost.push(pid);
+            }
+        }
+        self.remove(pid);
+    }
+}
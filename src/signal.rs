@@ -0,0 +1,131 @@
+//! Typed signal numbers and signal sets
+
+use std::fmt;
+
+macro_rules! signals {
+    ($($name:ident = $number:ident,)*) => {
+        /// A signal, as known to `redox_syscall`
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[repr(usize)]
+        pub enum Signal {
+            $($name = syscall::$number,)*
+        }
+        impl Signal {
+            /// Every known signal, in order
+            pub const ALL: &'static [Signal] = &[$(Signal::$name,)*];
+
+            /// Look up a signal by its number
+            pub fn from_number(number: usize) -> Option<Self> {
+                match number {
+                    $(syscall::$number => Some(Signal::$name),)*
+                    _ => None,
+                }
+            }
+            /// Returns the name of this signal, such as "SIGSEGV"
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Signal::$name => stringify!($number),)*
+                }
+            }
+        }
+    };
+}
+
+signals! {
+    Hup = SIGHUP,
+    Int = SIGINT,
+    Quit = SIGQUIT,
+    Ill = SIGILL,
+    Trap = SIGTRAP,
+    Abrt = SIGABRT,
+    Bus = SIGBUS,
+    Fpe = SIGFPE,
+    Kill = SIGKILL,
+    Usr1 = SIGUSR1,
+    Segv = SIGSEGV,
+    Usr2 = SIGUSR2,
+    Pipe = SIGPIPE,
+    Alrm = SIGALRM,
+    Term = SIGTERM,
+    Stkflt = SIGSTKFLT,
+    Chld = SIGCHLD,
+    Cont = SIGCONT,
+    Stop = SIGSTOP,
+    Tstp = SIGTSTP,
+    Ttin = SIGTTIN,
+    Ttou = SIGTTOU,
+    Urg = SIGURG,
+    Xcpu = SIGXCPU,
+    Xfsz = SIGXFSZ,
+    Vtalrm = SIGVTALRM,
+    Prof = SIGPROF,
+    Winch = SIGWINCH,
+    Io = SIGIO,
+    Pwr = SIGPWR,
+    Sys = SIGSYS,
+}
+
+impl Signal {
+    /// Returns the number of this signal
+    pub fn number(self) -> usize {
+        self as usize
+    }
+    /// Look up a signal by its name, with or without the "SIG"
+    /// prefix, such as "SIGSEGV" or "segv"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|signal| &signal.name()[3..] == name)
+    }
+}
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Display a signal number, by name if it is known
+pub struct SignalNumber(pub usize);
+
+impl fmt::Display for SignalNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Signal::from_number(self.0) {
+            Some(signal) => write!(f, "{}", signal),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// A set of signals as passed to `sigprocmask` and `sigaction`, where
+/// signal `n` is bit `n - 1`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SigSet(pub [u64; 2]);
+
+impl SigSet {
+    /// Returns true if the set contains the signal with this number
+    pub fn contains(&self, number: usize) -> bool {
+        match number.checked_sub(1) {
+            Some(bit) if bit < 128 => self.0[bit / 64] & (1 << (bit % 64)) != 0,
+            _ => false,
+        }
+    }
+    /// Returns an iterator over the numbers of all signals in the set
+    pub fn numbers(self) -> impl Iterator<Item = usize> {
+        (1..=128).filter(move |&number| self.contains(number))
+    }
+}
+impl fmt::Display for SigSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, number) in self.numbers().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", SignalNumber(number))?;
+        }
+        write!(f, "]")
+    }
+}
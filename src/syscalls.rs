@@ -0,0 +1,716 @@
+//! A declarative table of syscall signatures, and the typed decoding
+//! built on top of it. Rather than formatting a syscall straight to a
+//! string, the arguments are decoded into `Arg` values which can be
+//! inspected by filters, exporters and statistics before (if ever)
+//! being rendered.
+
+use bitflags::bitflags;
+use std::fmt;
+
+use crate::{
+    fds::FdTable,
+    kernel::debug::{ByteStr, HexStr},
+    plain::PlainData,
+    signal::{SigSet, SignalNumber},
+    Memory,
+};
+use syscall::{
+    data::{Map, SigAction, Stat, TimeSpec},
+    flag::*,
+    number::*,
+};
+
+/// How a syscall argument should be interpreted. Some kinds, such as
+/// paths and buffers, span two registers: a pointer and a length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ArgKind {
+    /// An unsigned integer
+    Int,
+    /// A signed integer, such as an offset
+    Signed,
+    /// An address or other value best shown in hexadecimal
+    Hex,
+    /// Permission bits, shown in octal
+    Mode,
+    /// A file descriptor
+    Fd,
+    /// A path, given as pointer and length
+    Path,
+    /// A byte buffer read by the kernel, given as pointer and length
+    Bytes,
+    /// A byte buffer the kernel writes to, given as pointer and
+    /// length
+    OutBytes,
+    /// A byte buffer whose contents are not decoded, given as pointer
+    /// and length
+    Buffer,
+    /// `O_*` flags
+    OpenFlags,
+    /// `CloneFlags`
+    CloneFlags,
+    /// `MapFlags`
+    MapFlags,
+    /// `PhysmapFlags`
+    PhysmapFlags,
+    /// `WaitFlags`
+    WaitFlags,
+    /// One of the `SEEK_*` constants
+    Whence,
+    /// One of the `F_*` fcntl commands
+    FcntlCmd,
+    /// A slice of `Map`, given as pointer and length in bytes
+    Maps,
+    /// An out-pointer to a `Stat`, followed by its size
+    Stat,
+    /// A pointer to a `TimeSpec` read by the kernel
+    TimeSpec,
+    /// An out-pointer to a `TimeSpec`
+    OutTimeSpec,
+    /// A slice of `TimeSpec`, given as pointer and length in bytes
+    TimeSpecs,
+    /// A futex address, shown along with the value it points to
+    Futex,
+    /// An out-pointer to a pair of file descriptors
+    FdPair,
+    /// A slice of `[ptr, len]` strings, given as pointer and count
+    StrList,
+    /// A signal number
+    Signal,
+    /// One of the `SIG_BLOCK`, `SIG_UNBLOCK` and `SIG_SETMASK`
+    /// constants
+    SigHow,
+    /// A pointer to a signal set read by the kernel
+    SigSet,
+    /// An out-pointer to a signal set
+    OutSigSet,
+    /// A pointer to a `SigAction` read by the kernel
+    SigAction,
+    /// An out-pointer to a `SigAction`
+    OutSigAction,
+}
+impl ArgKind {
+    /// Returns the number of registers this argument occupies
+    pub fn width(self) -> usize {
+        match self {
+            ArgKind::Path
+            | ArgKind::Bytes
+            | ArgKind::OutBytes
+            | ArgKind::Buffer
+            | ArgKind::Maps
+            | ArgKind::Stat
+            | ArgKind::TimeSpecs
+            | ArgKind::StrList => 2,
+            _ => 1,
+        }
+    }
+    /// Returns true if the kernel writes to this argument, meaning it
+    /// can only be read once the syscall has returned
+    pub fn is_output(self) -> bool {
+        matches!(
+            self,
+            ArgKind::OutBytes
+                | ArgKind::Stat
+                | ArgKind::OutTimeSpec
+                | ArgKind::FdPair
+                | ArgKind::OutSigSet
+                | ArgKind::OutSigAction
+        )
+    }
+    /// Decode this argument from the registers it occupies at syscall
+    /// entry, reading any pointed-to memory if `mem` is available.
+    /// No more than `limit` bytes of a byte buffer are read. Output
+    /// arguments are left unread, see `decode_output`.
+    pub fn decode(self, mem: Option<&mut Memory>, regs: &[usize], limit: usize) -> Arg {
+        self.decode_phase(mem, regs, None, limit)
+    }
+    /// Decode this argument at syscall exit, after a successful call
+    /// returned `ret`. Unlike `decode`, this reads output arguments.
+    /// Output buffers are limited to `ret` bytes.
+    pub fn decode_output(
+        self,
+        mem: Option<&mut Memory>,
+        regs: &[usize],
+        ret: usize,
+        limit: usize,
+    ) -> Arg {
+        self.decode_phase(mem, regs, Some(ret), limit)
+    }
+    fn decode_phase(
+        self,
+        mut mem: Option<&mut Memory>,
+        regs: &[usize],
+        ret: Option<usize>,
+        limit: usize,
+    ) -> Arg {
+        let a = regs.first().copied().unwrap_or(0);
+        let b = regs.get(1).copied().unwrap_or(0);
+        let mem = &mut mem;
+
+        match self {
+            ArgKind::Int => Arg::Int(a),
+            ArgKind::Signed => Arg::Signed(a as isize),
+            ArgKind::Hex => Arg::Hex(a),
+            ArgKind::Mode => Arg::Mode(a),
+            ArgKind::Fd => Arg::Fd(a),
+            ArgKind::Path => Arg::Path(Buffer {
+                addr: a,
+                len: b,
+                data: read(mem, a, b),
+                truncated: false,
+            }),
+            ArgKind::Bytes => Arg::Bytes(Buffer {
+                addr: a,
+                len: b,
+                data: read(mem, a, b.min(limit)),
+                truncated: b > limit,
+            }),
+            ArgKind::OutBytes => {
+                let len = ret.map_or(0, |ret| b.min(ret));
+                Arg::Bytes(Buffer {
+                    addr: a,
+                    len: b,
+                    data: ret.and_then(|_| read(mem, a, len.min(limit))),
+                    truncated: len > limit,
+                })
+            },
+            ArgKind::Buffer => Arg::Bytes(Buffer {
+                addr: a,
+                len: b,
+                data: None,
+                truncated: false,
+            }),
+            ArgKind::OpenFlags => Arg::OpenFlags(a),
+            ArgKind::CloneFlags => Arg::CloneFlags(CloneFlags::from_bits_truncate(a)),
+            ArgKind::MapFlags => Arg::MapFlags(MapFlags::from_bits_truncate(a)),
+            ArgKind::PhysmapFlags => Arg::PhysmapFlags(PhysmapFlags::from_bits_truncate(a)),
+            ArgKind::WaitFlags => Arg::WaitFlags(WaitFlags::from_bits_truncate(a)),
+            ArgKind::Whence => Arg::Whence(a),
+            ArgKind::FcntlCmd => Arg::FcntlCmd(a),
+            ArgKind::Maps => Arg::Maps(Pointer {
+                addr: a,
+                value: read(mem, a, b / std::mem::size_of::<Map>()),
+            }),
+            ArgKind::Stat => Arg::Stat(Pointer {
+                addr: a,
+                value: ret.and_then(|_| read_one(mem, a)),
+            }),
+            ArgKind::TimeSpec => Arg::TimeSpec(Pointer {
+                addr: a,
+                value: read_one(mem, a),
+            }),
+            ArgKind::OutTimeSpec => Arg::TimeSpec(Pointer {
+                addr: a,
+                value: ret.and_then(|_| read_one(mem, a)),
+            }),
+            ArgKind::TimeSpecs => Arg::TimeSpecs(Pointer {
+                addr: a,
+                value: read(mem, a, b / std::mem::size_of::<TimeSpec>()),
+            }),
+            ArgKind::Futex => Arg::Futex(Pointer {
+                addr: a,
+                value: read_one(mem, a),
+            }),
+            ArgKind::FdPair => Arg::FdPair(Pointer {
+                addr: a,
+                value: ret.and_then(|_| read_one(mem, a)),
+            }),
+            ArgKind::StrList => Arg::StrList(Pointer {
+                addr: a,
+                value: read::<[usize; 2]>(mem, a, b).map(|slice| {
+                    slice
+                        .iter()
+                        .map(|s| read(mem, s[0], s[1]).and_then(|s| String::from_utf8(s).ok()))
+                        .collect()
+                }),
+            }),
+            ArgKind::Signal => Arg::Signal(a),
+            ArgKind::SigHow => Arg::SigHow(a),
+            ArgKind::SigSet => Arg::SigSet(Pointer {
+                addr: a,
+                value: read_one(mem, a).map(SigSet),
+            }),
+            ArgKind::OutSigSet => Arg::SigSet(Pointer {
+                addr: a,
+                value: ret.and_then(|_| read_one(mem, a)).map(SigSet),
+            }),
+            ArgKind::SigAction => Arg::SigAction(Pointer {
+                addr: a,
+                value: read_one(mem, a),
+            }),
+            ArgKind::OutSigAction => Arg::SigAction(Pointer {
+                addr: a,
+                value: ret.and_then(|_| read_one(mem, a)),
+            }),
+        }
+    }
+}
+
+bitflags! {
+    /// Groups of related syscalls, useful for filtering
+    pub struct SyscallClass: u8 {
+        /// Takes a path
+        const FILE = 1 << 0;
+        /// Takes or returns a file descriptor
+        const DESC = 1 << 1;
+        /// Maps or manages memory
+        const MEMORY = 1 << 2;
+        /// Creates, replaces, waits for or ends processes
+        const PROCESS = 1 << 3;
+        /// Deals with signals
+        const SIGNAL = 1 << 4;
+        /// Gets or sets user and group IDs
+        const CREDS = 1 << 5;
+        /// Reads clocks or sleeps
+        const CLOCK = 1 << 6;
+    }
+}
+impl SyscallClass {
+    /// Look up a class by its lowercase name, such as "file"
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "file" => Self::FILE,
+            "desc" => Self::DESC,
+            "memory" => Self::MEMORY,
+            "process" => Self::PROCESS,
+            "signal" => Self::SIGNAL,
+            "creds" => Self::CREDS,
+            "clock" => Self::CLOCK,
+            _ => return None,
+        })
+    }
+}
+
+/// The signature of one syscall
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SyscallDef {
+    pub number: usize,
+    pub name: &'static str,
+    pub args: &'static [ArgKind],
+    pub class: SyscallClass,
+}
+
+macro_rules! syscalls {
+    ($($number:ident => $name:ident($($kind:ident),*) $(in $($class:ident)|+)?;)*) => {
+        /// All syscalls known to the decoder
+        pub static SYSCALLS: &[SyscallDef] = &[
+            $(SyscallDef {
+                number: $number,
+                name: stringify!($name),
+                args: &[$(ArgKind::$kind),*],
+                class: SyscallClass::from_bits_truncate(0 $($(| SyscallClass::$class.bits())+)?),
+            },)*
+        ];
+    };
+}
+
+syscalls! {
+    SYS_OPEN => open(Path, OpenFlags) in FILE | DESC;
+    SYS_CHMOD => chmod(Path, Mode) in FILE;
+    SYS_RMDIR => rmdir(Path) in FILE;
+    SYS_UNLINK => unlink(Path) in FILE;
+    SYS_CLOSE => close(Fd) in DESC;
+    SYS_DUP => dup(Fd, Bytes) in DESC;
+    SYS_DUP2 => dup2(Fd, Fd, Bytes) in DESC;
+    SYS_READ => read(Fd, OutBytes) in DESC;
+    SYS_WRITE => write(Fd, Bytes) in DESC;
+    SYS_LSEEK => lseek(Fd, Signed, Whence) in DESC;
+    SYS_FCHMOD => fchmod(Fd, Mode) in DESC;
+    SYS_FCHOWN => fchown(Fd, Int, Int) in DESC;
+    SYS_FCNTL => fcntl(Fd, FcntlCmd, Hex) in DESC;
+    SYS_FMAP => fmap(Fd, Maps) in DESC | MEMORY;
+    SYS_FUNMAP => funmap(Hex) in MEMORY;
+    SYS_FPATH => fpath(Fd, OutBytes) in DESC;
+    SYS_FRENAME => frename(Fd, Path) in FILE | DESC;
+    SYS_FSTAT => fstat(Fd, Stat) in DESC;
+    SYS_FSTATVFS => fstatvfs(Fd, Buffer) in DESC;
+    SYS_FSYNC => fsync(Fd) in DESC;
+    SYS_FTRUNCATE => ftruncate(Fd, Int) in DESC;
+    SYS_FUTIMENS => futimens(Fd, TimeSpecs) in DESC;
+
+    SYS_BRK => brk(Hex) in MEMORY;
+    SYS_CHDIR => chdir(Path) in FILE;
+    SYS_CLOCK_GETTIME => clock_gettime(Int, OutTimeSpec) in CLOCK;
+    SYS_CLONE => clone(CloneFlags) in PROCESS;
+    SYS_EXIT => exit(Int) in PROCESS;
+    SYS_FEXEC => fexec(Fd, StrList, StrList) in DESC | PROCESS;
+    SYS_FUTEX => futex(Futex, Int, Int, Int, Int);
+    SYS_GETCWD => getcwd(OutBytes);
+    SYS_GETEGID => getegid() in CREDS;
+    SYS_GETENS => getens();
+    SYS_GETEUID => geteuid() in CREDS;
+    SYS_GETGID => getgid() in CREDS;
+    SYS_GETNS => getns();
+    SYS_GETPGID => getpgid();
+    SYS_GETPID => getpid();
+    SYS_GETPPID => getppid();
+    SYS_GETUID => getuid() in CREDS;
+    SYS_IOPL => iopl(Int);
+    SYS_KILL => kill(Int, Signal) in PROCESS | SIGNAL;
+    SYS_SIGRETURN => sigreturn() in SIGNAL;
+    SYS_SIGACTION => sigaction(Signal, SigAction, OutSigAction, Hex) in SIGNAL;
+    SYS_SIGPROCMASK => sigprocmask(SigHow, SigSet, OutSigSet) in SIGNAL;
+    SYS_MKNS => mkns(StrList);
+    SYS_MPROTECT => mprotect(Hex, Int, MapFlags) in MEMORY;
+    SYS_NANOSLEEP => nanosleep(TimeSpec, OutTimeSpec) in CLOCK;
+    SYS_PHYSALLOC => physalloc(Int) in MEMORY;
+    SYS_PHYSFREE => physfree(Hex, Int) in MEMORY;
+    SYS_PHYSMAP => physmap(Hex, Int, PhysmapFlags) in MEMORY;
+    SYS_PHYSUNMAP => physunmap(Hex) in MEMORY;
+    SYS_VIRTTOPHYS => virttophys(Hex) in MEMORY;
+    SYS_PIPE2 => pipe2(FdPair, OpenFlags) in DESC;
+    SYS_SETREGID => setregid(Int, Int) in CREDS;
+    SYS_SETRENS => setrens(Int, Int);
+    SYS_SETREUID => setreuid(Int, Int) in CREDS;
+    SYS_UMASK => umask(Mode);
+    SYS_WAITPID => waitpid(Int, Hex, WaitFlags) in PROCESS;
+    SYS_YIELD => yield();
+}
+
+/// Look up the signature of a syscall by its number
+pub fn lookup(number: usize) -> Option<&'static SyscallDef> {
+    SYSCALLS.iter().find(|def| def.number == number)
+}
+
+/// Look up the signature of a syscall by its name, such as "open"
+pub fn lookup_name(name: &str) -> Option<&'static SyscallDef> {
+    SYSCALLS.iter().find(|def| def.name == name)
+}
+
+/// A pointer argument, along with the value it pointed to if it
+/// could be read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pointer<T> {
+    pub addr: usize,
+    pub value: Option<T>,
+}
+
+/// A byte buffer argument given as pointer and length, along with its
+/// contents if they were read.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Buffer {
+    pub addr: usize,
+    pub len: usize,
+    pub data: Option<Vec<u8>>,
+    /// Set if only the start of the contents was read, see
+    /// `ArgKind::decode`
+    pub truncated: bool,
+}
+
+/// One decoded syscall argument
+#[derive(Clone, Debug)]
+pub enum Arg {
+    Int(usize),
+    Signed(isize),
+    Hex(usize),
+    Mode(usize),
+    Fd(usize),
+    Path(Buffer),
+    Bytes(Buffer),
+    OpenFlags(usize),
+    CloneFlags(CloneFlags),
+    MapFlags(MapFlags),
+    PhysmapFlags(PhysmapFlags),
+    WaitFlags(WaitFlags),
+    Whence(usize),
+    FcntlCmd(usize),
+    Maps(Pointer<Vec<Map>>),
+    Stat(Pointer<Stat>),
+    TimeSpec(Pointer<TimeSpec>),
+    TimeSpecs(Pointer<Vec<TimeSpec>>),
+    Futex(Pointer<i32>),
+    FdPair(Pointer<[usize; 2]>),
+    StrList(Pointer<Vec<Option<String>>>),
+    Signal(usize),
+    SigHow(usize),
+    SigSet(Pointer<SigSet>),
+    SigAction(Pointer<SigAction>),
+}
+
+/// A syscall with its arguments decoded according to `SYSCALLS`
+#[derive(Clone, Debug)]
+pub struct DecodedSyscall {
+    pub number: usize,
+    /// The name of the syscall, or `None` if it's not in the table
+    pub name: Option<&'static str>,
+    pub args: Vec<Arg>,
+    /// The raw argument registers the syscall was decoded from
+    pub raw: Vec<usize>,
+}
+
+fn read<T: PlainData>(mem: &mut Option<&mut Memory>, addr: usize, len: usize) -> Option<Vec<T>> {
+    mem.as_deref_mut()?.read_vec(addr as *const T, len).ok()
+}
+fn read_one<T: PlainData>(mem: &mut Option<&mut Memory>, addr: usize) -> Option<T> {
+    mem.as_deref_mut()?.read_value(addr as *const T).ok()
+}
+
+impl DecodedSyscall {
+    /// Read the output arguments of this syscall, such as the `Stat`
+    /// filled in by `fstat`. Call this at the post-syscall stop with
+    /// the return value, as they're only filled in once the kernel
+    /// has handled the syscall. Nothing is read if the syscall failed.
+    /// Byte buffers are read up to `limit` bytes.
+    pub fn decode_outputs(&mut self, mut mem: Option<&mut Memory>, ret: usize, limit: usize) {
+        let def = match lookup(self.number) {
+            Some(def) => def,
+            None => return,
+        };
+        let ret = match syscall::Error::demux(ret) {
+            Ok(ret) => ret,
+            Err(_) => return,
+        };
+
+        let mut i = 0;
+        for (kind, arg) in def.args.iter().zip(&mut self.args) {
+            if kind.is_output() {
+                let regs = self.raw.get(i..).unwrap_or(&[]);
+                *arg = kind.decode_output(mem.as_deref_mut(), regs, ret, limit);
+            }
+            i += kind.width();
+        }
+    }
+}
+
+/// Decode a syscall from its number and argument registers, reading
+/// any pointed-to memory if `mem` is available. Byte buffers are read
+/// up to `limit` bytes.
+pub fn decode(
+    mut mem: Option<&mut Memory>,
+    number: usize,
+    regs: &[usize],
+    limit: usize,
+) -> DecodedSyscall {
+    let def = match lookup(number) {
+        Some(def) => def,
+        None => {
+            return DecodedSyscall {
+                number,
+                name: None,
+                args: regs.iter().map(|&reg| Arg::Hex(reg)).collect(),
+                raw: regs.to_vec(),
+            }
+        },
+    };
+
+    let mut args = Vec::with_capacity(def.args.len());
+    let mut i = 0;
+    for kind in def.args {
+        let regs = regs.get(i..).unwrap_or(&[]);
+        args.push(kind.decode(mem.as_deref_mut(), regs, limit));
+        i += kind.width();
+    }
+
+    DecodedSyscall {
+        number,
+        name: Some(def.name),
+        args,
+        raw: regs.to_vec(),
+    }
+}
+
+const OPEN_FLAGS: &[(usize, &str)] = &[
+    (O_NONBLOCK, "O_NONBLOCK"),
+    (O_APPEND, "O_APPEND"),
+    (O_SHLOCK, "O_SHLOCK"),
+    (O_EXLOCK, "O_EXLOCK"),
+    (O_ASYNC, "O_ASYNC"),
+    (O_FSYNC, "O_FSYNC"),
+    (O_CLOEXEC, "O_CLOEXEC"),
+    (O_CREAT, "O_CREAT"),
+    (O_TRUNC, "O_TRUNC"),
+    (O_EXCL, "O_EXCL"),
+    (O_DIRECTORY, "O_DIRECTORY"),
+    (O_STAT, "O_STAT"),
+    (O_SYMLINK, "O_SYMLINK"),
+    (O_NOFOLLOW, "O_NOFOLLOW"),
+];
+
+fn fmt_open_flags(f: &mut fmt::Formatter, flags: usize) -> fmt::Result {
+    let mut names = Vec::new();
+    match flags & O_ACCMODE {
+        O_RDONLY => names.push("O_RDONLY".to_string()),
+        O_WRONLY => names.push("O_WRONLY".to_string()),
+        O_RDWR => names.push("O_RDWR".to_string()),
+        _ => (),
+    }
+    let mut rest = flags & !O_ACCMODE;
+    for &(flag, name) in OPEN_FLAGS {
+        if rest & flag == flag {
+            names.push(name.to_string());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 {
+        names.push(format!("{:#X}", rest));
+    }
+    if names.is_empty() {
+        names.push(String::from("0"));
+    }
+    write!(f, "{}", names.join("|"))
+}
+
+fn fmt_pointer<T, F>(f: &mut fmt::Formatter, ptr: &Pointer<T>, fmt_value: F) -> fmt::Result
+where
+    F: FnOnce(&mut fmt::Formatter, &T) -> fmt::Result,
+{
+    match ptr.value {
+        Some(ref value) => fmt_value(f, value),
+        None => write!(f, "{:#X}", ptr.addr),
+    }
+}
+
+/// How many bytes of a buffer are shown by default
+pub const DEFAULT_STRING_LIMIT: usize = 32;
+
+/// Options controlling how decoded syscalls are rendered as text
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderOptions<'a> {
+    /// Truncate byte buffers after this many bytes
+    pub string_limit: Option<usize>,
+    /// Show byte buffers entirely as hex escapes
+    pub hex: bool,
+    /// Annotate file descriptors with their paths, such as
+    /// `5<file:/etc/hostname>`
+    pub fds: Option<&'a FdTable>,
+}
+
+impl Arg {
+    /// Render this argument using the specified options. The
+    /// `Display` implementation uses the default options.
+    pub fn fmt_with(&self, f: &mut fmt::Formatter, opts: &RenderOptions) -> fmt::Result {
+        match self {
+            Arg::Int(n) => write!(f, "{}", n),
+            Arg::Signed(n) => write!(f, "{}", n),
+            Arg::Hex(n) => write!(f, "{:#X}", n),
+            Arg::Mode(n) => write!(f, "{:#o}", n),
+            Arg::Fd(fd) => match opts.fds.and_then(|fds| fds.get(*fd)) {
+                Some(path) => write!(f, "{}<{}>", fd, path),
+                None => write!(f, "{}", fd),
+            },
+            Arg::Path(buf) => match buf.data {
+                Some(ref data) => write!(f, "{:?}", ByteStr(data)),
+                None => write!(f, "{:#X}, {}", buf.addr, buf.len),
+            },
+            Arg::Bytes(buf) => match buf.data {
+                Some(ref data) => {
+                    let shown = match opts.string_limit {
+                        Some(limit) if data.len() > limit => &data[..limit],
+                        _ => &data[..],
+                    };
+                    if opts.hex {
+                        write!(f, "{:?}", HexStr(shown))?;
+                    } else {
+                        write!(f, "{:?}", ByteStr(shown))?;
+                    }
+                    if shown.len() < data.len() || buf.truncated {
+                        write!(f, "...")?;
+                    }
+                    write!(f, ", {}", buf.len)
+                },
+                None => write!(f, "{:#X}, {}", buf.addr, buf.len),
+            },
+            Arg::OpenFlags(flags) => fmt_open_flags(f, *flags),
+            Arg::CloneFlags(flags) => write!(f, "{:?}", flags),
+            Arg::MapFlags(flags) => write!(f, "{:?}", flags),
+            Arg::PhysmapFlags(flags) => write!(f, "{:?}", flags),
+            Arg::WaitFlags(flags) => write!(f, "{:?}", flags),
+            Arg::Whence(whence) => match *whence {
+                SEEK_SET => write!(f, "SEEK_SET"),
+                SEEK_CUR => write!(f, "SEEK_CUR"),
+                SEEK_END => write!(f, "SEEK_END"),
+                other => write!(f, "UNKNOWN ({})", other),
+            },
+            Arg::FcntlCmd(cmd) => match *cmd {
+                F_DUPFD => write!(f, "F_DUPFD"),
+                F_GETFD => write!(f, "F_GETFD"),
+                F_SETFD => write!(f, "F_SETFD"),
+                F_SETFL => write!(f, "F_SETFL"),
+                F_GETFL => write!(f, "F_GETFL"),
+                other => write!(f, "UNKNOWN ({})", other),
+            },
+            Arg::Maps(ptr) => fmt_pointer(f, ptr, |f, maps| write!(f, "{:?}", maps)),
+            Arg::Stat(ptr) => fmt_pointer(f, ptr, |f, stat| write!(f, "{:?}", stat)),
+            Arg::TimeSpec(ptr) => fmt_pointer(f, ptr, |f, time| write!(f, "{:?}", time)),
+            Arg::TimeSpecs(ptr) => fmt_pointer(f, ptr, |f, times| write!(f, "{:?}", times)),
+            Arg::Futex(ptr) => {
+                write!(f, "{:#X}", ptr.addr)?;
+                if let Some(value) = ptr.value {
+                    write!(f, " [{}]", value)?;
+                }
+                Ok(())
+            },
+            Arg::FdPair(ptr) => fmt_pointer(f, ptr, |f, fds| write!(f, "{:?}", fds)),
+            Arg::StrList(ptr) => fmt_pointer(f, ptr, |f, list| write!(f, "{:?}", list)),
+            Arg::Signal(signal) => write!(f, "{}", SignalNumber(*signal)),
+            Arg::SigHow(how) => match *how {
+                SIG_BLOCK => write!(f, "SIG_BLOCK"),
+                SIG_UNBLOCK => write!(f, "SIG_UNBLOCK"),
+                SIG_SETMASK => write!(f, "SIG_SETMASK"),
+                how => write!(f, "{}", how),
+            },
+            Arg::SigSet(ptr) => fmt_pointer(f, ptr, |f, set| write!(f, "{}", set)),
+            Arg::SigAction(ptr) => fmt_pointer(f, ptr, |f, action| {
+                write!(f, "{{sa_handler=")?;
+                match action.sa_handler.map_or(0, |handler| handler as usize) {
+                    SIG_DFL => write!(f, "SIG_DFL")?,
+                    SIG_IGN => write!(f, "SIG_IGN")?,
+                    handler => write!(f, "{:#X}", handler)?,
+                }
+                write!(
+                    f,
+                    ", sa_mask={}, sa_flags={:?}}}",
+                    SigSet(action.sa_mask),
+                    action.sa_flags
+                )
+            }),
+        }
+    }
+}
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_with(f, &RenderOptions::default())
+    }
+}
+
+impl DecodedSyscall {
+    /// Render this syscall using the specified options. The `Display`
+    /// implementation uses the default options.
+    pub fn fmt_with(&self, f: &mut fmt::Formatter, opts: &RenderOptions) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{}(", name)?,
+            None => write!(f, "UNKNOWN{} {:#X}(", self.number, self.number)?,
+        }
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            arg.fmt_with(f, opts)?;
+        }
+        write!(f, ")")
+    }
+    /// Returns a value which displays this syscall using the specified
+    /// options
+    pub fn display<'a>(&'a self, opts: RenderOptions<'a>) -> SyscallDisplay<'a> {
+        SyscallDisplay {
+            syscall: self,
+            opts,
+        }
+    }
+}
+impl fmt::Display for DecodedSyscall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_with(f, &RenderOptions::default())
+    }
+}
+
+/// A syscall along with the options to render it with, see
+/// `DecodedSyscall::display`
+pub struct SyscallDisplay<'a> {
+    syscall: &'a DecodedSyscall,
+    opts: RenderOptions<'a>,
+}
+impl<'a> fmt::Display for SyscallDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.syscall.fmt_with(f, &self.opts)
+    }
+}